@@ -3,14 +3,21 @@
 #![allow(unused)]
 use std::mem;
 use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
 
+use crate::arena::GrowthPolicy;
 use crate::token::Token;
 
+// source of the ids that tell the allocators (and their tokens) apart
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Clone, Debug)]
 pub struct Allocator<T> {
     data: Vec<Cell<T>>,
     head: Option<NonZeroUsize>,
-    len: usize
+    len: usize,
+    id: u64,
+    policy: GrowthPolicy
 }
 
 #[derive(Clone, Debug)]
@@ -20,13 +27,7 @@ enum Cell<T> {
 }
 
 impl<T> Default for Allocator<T> {
-    fn default() -> Self {
-        Allocator {
-            data: vec![Cell::Nothing(None)],
-            head: Some(NonZeroUsize::new(1).unwrap()),
-            len: 0
-        }
-    }
+    fn default() -> Self { Allocator::new() }
 }
 
 impl<T> Allocator<T> {
@@ -34,15 +35,31 @@ impl<T> Allocator<T> {
         Allocator {
             data: vec![Cell::Nothing(None)],
             head: Some(NonZeroUsize::new(1).unwrap()),
-            len: 0
+            len: 0,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            policy: GrowthPolicy::Double
         }
     }
 
+    fn token(&self, index: NonZeroUsize) -> Token {
+        Token { index, arena_id: self.id }
+    }
+
+    // zero-based index of the cell the token points to
+    fn cell_index(&self, token: Token) -> Option<usize> {
+        match token.arena_id == self.id {
+            true => Some(token.index.get() - 1),
+            false => None
+        }
+    }
+
+    pub fn owns(&self, token: Token) -> bool { token.arena_id == self.id }
+
     pub fn head(&mut self) -> Token {
         match self.head {
-            Some(head) => Token{ index: head },
+            Some(head) => self.token(head),
             None => {
-                self.reserve(self.len());
+                self.grow();
                 self.head()
             }
         }
@@ -54,28 +71,63 @@ impl<T> Allocator<T> {
 
     pub fn capacity(&self) -> usize { self.data.len() }
 
+    // number of consecutive free cells at the tail of the backing storage
+    pub fn reclaimable(&self) -> usize {
+        self.data.iter().rev()
+            .take_while(|cell| match cell {
+                Cell::Nothing(_) => true,
+                Cell::Just(_) => false
+            })
+            .count()
+    }
+
     pub fn is_valid_token(&self, token: Token) -> bool {
         self.get(token).is_some()
     }
 
     fn find_last_available(&self) -> Option<NonZeroUsize> {
-        fn aux<T>(data: &[Cell<T>], indx: NonZeroUsize) -> Option<NonZeroUsize> {
-            match data.get(indx.get() - 1) {  // get back to zero-based indexing
+        // walk the heap til the end
+        let mut indx = self.head?;
+        loop {
+            match self.data.get(indx.get() - 1) {  // get back to zero-based indexing
                 Some(Cell::Just(_)) | None => panic!("corrpt arena"),
                 Some(Cell::Nothing(next_head)) => match next_head {
-                    Some(n) => aux(data, *n),
-                    None => Some(indx)
+                    Some(n) => indx = *n,
+                    None => break Some(indx)
                 }
             }
         }
-        match self.head {
-            None => None,
-            Some(head) => aux(&self.data[..], head) // walk the heap til the end
-        }
+    }
+
+    pub fn set_growth_policy(&mut self, policy: GrowthPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn growth_policy(&self) -> GrowthPolicy { self.policy }
+
+    // grow the storage according to the growth policy
+    fn grow(&mut self) {
+        let additional = match self.policy {
+            GrowthPolicy::Double => self.capacity(),
+            GrowthPolicy::Fixed(n) => n
+        };
+        self.reserve(additional.max(1));
     }
 
     pub fn reserve(&mut self, additional: usize) {
         self.data.reserve_exact(additional);
+        self.extend_free_list(additional);
+    }
+
+    pub fn reserve_amortized(&mut self, additional: usize) {
+        self.data.reserve(additional);
+        self.extend_free_list(additional);
+    }
+
+    // append free cells to the storage and chain them to the end of the free
+    // list
+    fn extend_free_list(&mut self, additional: usize) {
+        if additional == 0 { return }
         let head_indx = NonZeroUsize::new(self.data.len() + 1).unwrap();
         match self.find_last_available() {
             None => self.head = Some(head_indx),
@@ -88,10 +140,23 @@ impl<T> Allocator<T> {
         self.data.extend(new_cells);
     }
 
+    // rebuild the free list so that free cells are handed out in ascending
+    // order
+    pub fn sort_free_list(&mut self) {
+        let mut head = None;
+        for (i, cell) in self.data.iter_mut().enumerate().rev() {
+            if let Cell::Nothing(next_head) = cell {
+                *next_head = head;
+                head = NonZeroUsize::new(i + 1);
+            }
+        }
+        self.head = head;
+    }
+
     pub fn insert(&mut self, data: T) -> Token {
         match self.head {
             None => {
-                self.reserve(self.capacity());
+                self.grow();
                 self.insert(data)
             },
             Some(index) => {
@@ -103,7 +168,7 @@ impl<T> Allocator<T> {
                 self.head = *next_head;
                 self.len += 1;
                 self.data[i] = Cell::Just(data);
-                Token { index }
+                self.token(index)
             }
         }
     }
@@ -115,11 +180,12 @@ impl<T> Allocator<T> {
     }
 
     pub fn remove(&mut self, token: Token) -> Option<T> {
-        match self.data.get_mut(token.index.get() - 1) {  // zero-based index
+        let i = self.cell_index(token)?;
+        match self.data.get_mut(i) {
             Some(Cell::Nothing(_)) | None => None,
             Some(mut cell) => {
                 let mut x = Cell::Nothing(self.head);
-                mem::swap(&mut x, &mut cell);
+                mem::swap(&mut x, cell);
                 self.head = Some(token.index);
                 self.len -= 1;
                 match x {
@@ -130,17 +196,82 @@ impl<T> Allocator<T> {
         }
     }
 
+    pub fn get_two_mut(&mut self, a: Token, b: Token)
+        -> Option<(&mut T, &mut T)> {
+        let (i, j) = (self.cell_index(a)?, self.cell_index(b)?);
+        if i == j || i >= self.data.len() || j >= self.data.len() {
+            return None
+        }
+        let (low, high) = self.data.split_at_mut(i.max(j));
+        let (x, y) = match i < j {
+            true => (&mut low[i], &mut high[0]),
+            false => (&mut high[0], &mut low[j])
+        };
+        match (x, y) {
+            (Cell::Just(x), Cell::Just(y)) => Some((x, y)),
+            _ => None
+        }
+    }
+
+    pub fn get_by_index(&self, index: usize) -> Option<&T> {
+        let token = self.token(NonZeroUsize::new(index)?);
+        self.get(token)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.data.iter().filter_map(|cell| match cell {
+            Cell::Nothing(_) => None,
+            Cell::Just(data) => Some(data)
+        })
+    }
+
     pub fn get(&self, token: Token) -> Option<&T> {
-        match self.data.get(token.index.get() - 1) {  // zero-based index
+        match self.data.get(self.cell_index(token)?) {
             Some(Cell::Nothing(_)) | None => None,
             Some(Cell::Just(data)) => Some(data)
         }
     }
 
     pub fn get_mut(&mut self, token: Token) -> Option<&mut T> {
-        match self.data.get_mut(token.index.get() - 1) {  // zero-based index
+        let i = self.cell_index(token)?;
+        match self.data.get_mut(i) {
             Some(Cell::Nothing(_)) | None => None,
             Some(Cell::Just(data)) => Some(data)
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // number of times the backing storage is reallocated over many small
+    // reservations
+    fn reallocations(reserve: fn(&mut Allocator<usize>, usize)) -> usize {
+        let mut allocator = Allocator::new();
+        let mut count = 0;
+        for _ in 0..1000 {
+            let capacity = allocator.data.capacity();
+            reserve(&mut allocator, 3);
+            for i in 0..3 { allocator.insert(i); }
+            if allocator.data.capacity() != capacity { count += 1 }
+        }
+        count
+    }
+
+    #[test]
+    fn reserve_amortized() {
+        assert!(reallocations(Allocator::reserve_amortized) < 20);
+        assert_eq!(reallocations(Allocator::reserve), 1000);
+
+        // the free list stays intact
+        let mut allocator = Allocator::new();
+        allocator.reserve_amortized(5);
+        allocator.reserve_amortized(0);
+        let tokens: Vec<_> = (0..6).map(|i| allocator.insert(i)).collect();
+        assert_eq!(allocator.capacity(), 6);
+        for (i, &token) in tokens.iter().enumerate() {
+            assert_eq!(allocator.get(token), Some(&i));
+        }
+    }
+}
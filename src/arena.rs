@@ -1,23 +1,113 @@
 #![allow(clippy::match_bool)]
-use std::collections::HashMap;
-use std::num::NonZeroUsize;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::mem;
 use std::ops::{Index, IndexMut};
 
 use crate::alloc::Allocator;
-use crate::iter::{Branch, ChildrenTokens};
+use crate::cursor::{Cursor, CursorMut, Zipper};
+use crate::iter::{Branch, Nodes, TraversalOrder, preorder_next, postorder_next};
 use crate::node::Node;
-use crate::token::Token;
+use crate::token::{StableId, Token};
+use crate::Error;
 
 /// A struct that provides the arena allocator.
-#[derive(Debug, Default, Clone)]
+///
+/// Nodes are stored flat in the arena rather than owned by their parents, so
+/// dropping an arena (or removing a subtree from it) never recurses no matter
+/// how deep the trees are.
+#[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Arena<T> {
-    pub (crate) allocator: Allocator<Node<T>>
+    pub (crate) allocator: Allocator<Node<T>>,
+    stable_ids: HashMap<StableId, Token>,
+    next_stable_id: u64
+}
+
+/// A violation of the structure of the trees in an arena found by
+/// [`check_integrity`]. Each variant carries the token of the offending node.
+///
+/// [`check_integrity`]: struct.Arena.html#method.check_integrity
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntegrityError {
+    /// The node is not stored under its own token.
+    TokenMismatch(Token),
+    /// A link of the node refers to a node that is not in the arena.
+    DanglingLink(Token),
+    /// The sibling links of the node and its neighbors do not agree.
+    SiblingMismatch(Token),
+    /// The node and its parent (or its siblings) disagree on the parent.
+    ParentMismatch(Token),
+    /// The first or last child links of the node are inconsistent.
+    ChildMismatch(Token),
+    /// The node is part of a cycle or cannot be reached from any root.
+    Cycle(Token)
+}
+
+/// How an arena grows its storage when it runs out of free slots. See
+/// [`set_growth_policy`] for more.
+///
+/// [`set_growth_policy`]: struct.Arena.html#method.set_growth_policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GrowthPolicy {
+    /// Double the capacity (the default).
+    Double,
+    /// Grow the capacity by a fixed number of slots (at least 1).
+    Fixed(usize)
+}
+
+/// Statistics of an arena. See [`stats`] for more.
+///
+/// [`stats`]: struct.Arena.html#method.stats
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArenaStats {
+    /// The number of nodes in the arena.
+    pub node_count: usize,
+    /// The number of nodes the arena can hold without reallocating.
+    pub capacity: usize,
+    /// The number of trees in the arena.
+    pub root_count: usize,
+    /// The height (in edges) of the tallest tree in the arena.
+    pub max_height: usize,
+    /// The number of leaves in the arena.
+    pub leaf_count: usize
+}
+
+/// A difference between two trees found by [`diff`]. Nodes are matched by
+/// their positions, and each variant carries the path of child positions
+/// leading from the root of the compared trees to the node.
+///
+/// [`diff`]: struct.Arena.html#method.diff
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TreeDiff<T> {
+    /// The node is only in the second tree.
+    Added { path: Vec<usize>, data: T },
+    /// The node is only in the first tree.
+    Removed { path: Vec<usize>, data: T },
+    /// The node is in both trees but its data differs.
+    Changed { path: Vec<usize>, old: T, new: T }
 }
 
 impl<T> Arena<T> {
-    /// Initializes a new `Arena<T>`.
-    pub fn new() -> Self { Arena { allocator: Allocator::new() } }
+    /// Initializes a new empty `Arena<T>`. This is equivalent to
+    /// `Arena::default()`.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let arena = Arena::<i32>::new();
+    /// assert!(arena.is_empty());
+    /// assert_eq!(arena.node_count(), 0);
+    /// ```
+    pub fn new() -> Self {
+        Arena {
+            allocator: Allocator::new(),
+            stable_ids: HashMap::new(),
+            next_stable_id: 0
+        }
+    }
 
     /// Returns true if the arena is empty.
     ///
@@ -57,6 +147,142 @@ impl<T> Arena<T> {
     /// Returns the number of nodes the tree can hold without reallocating.
     pub fn capacity(&self) -> usize { self.allocator.capacity() }
 
+    /// Returns the number of free slots at the tail of the backing storage,
+    /// i.e. how much the capacity of the arena could be reduced by shrinking it
+    /// without moving any nodes. Slots freed in the middle of the storage do
+    /// not count.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = 1usize;
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let child = root_token.append(&mut arena, 2usize);
+    /// let grandchild = child.append(&mut arena, 3usize);
+    /// let reclaimable = arena.reclaimable();
+    ///
+    /// arena.uproot(grandchild);
+    /// assert_eq!(arena.reclaimable(), reclaimable + 1);
+    /// ```
+    pub fn reclaimable(&self) -> usize { self.allocator.reclaimable() }
+
+    /// Sets how the arena grows its storage once it runs out of free slots.
+    /// By default the capacity is doubled. A fixed growth policy trades more
+    /// frequent reallocations for a tighter memory footprint.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::{Arena, GrowthPolicy};
+    ///
+    /// let root_data = 1usize;
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// arena.set_growth_policy(GrowthPolicy::Fixed(4));
+    /// assert_eq!(arena.growth_policy(), GrowthPolicy::Fixed(4));
+    ///
+    /// root_token.append(&mut arena, 2usize);
+    /// assert_eq!(arena.capacity(), 5);
+    /// ```
+    pub fn set_growth_policy(&mut self, policy: GrowthPolicy) {
+        self.allocator.set_growth_policy(policy)
+    }
+
+    /// Returns the growth policy of the arena. See [`set_growth_policy`] for
+    /// more.
+    ///
+    /// [`set_growth_policy`]: struct.Arena.html#method.set_growth_policy
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        self.allocator.growth_policy()
+    }
+
+    /// Reserves room for at least `additional` more nodes. The backing storage
+    /// grows geometrically, like `Vec::reserve`, so reserving in many small
+    /// batches only reallocates a logarithmic number of times, at the cost of
+    /// possibly over-allocating memory.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = 1usize;
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// arena.reserve_amortized(10);
+    /// assert!(arena.capacity() >= 11);
+    ///
+    /// let capacity = arena.capacity();
+    /// for i in 0..10 {
+    ///     root_token.append(&mut arena, i);
+    /// }
+    /// assert_eq!(arena.capacity(), capacity);
+    /// ```
+    pub fn reserve_amortized(&mut self, additional: usize) {
+        self.allocator.reserve_amortized(additional)
+    }
+
+    /// Reorders the free slots of the arena so that they are reused lowest
+    /// first. Freed slots are otherwise reused in the reverse order of their
+    /// removal, which scatters new nodes across memory after many removals.
+    /// Existing nodes and their tokens are not affected.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let mut arena = Arena::new();
+    /// let tokens: Vec<_> = (0..4).map(|i| arena.new_node(i)).collect();
+    /// arena.uproot(tokens[1]);
+    /// arena.uproot(tokens[2]);
+    ///
+    /// arena.sort_free_list();
+    /// assert_eq!(arena.new_node(5), tokens[1]);
+    /// assert_eq!(arena.new_node(6), tokens[2]);
+    /// assert_eq!(arena[tokens[3]].data, 3);
+    /// ```
+    pub fn sort_free_list(&mut self) { self.allocator.sort_free_list() }
+
+    /// Checks whether the token was issued by this arena (or by the arena it
+    /// was cloned from). This does not check whether the node the token refers
+    /// to is still in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let (mut arena1, root1) = Arena::with_data("Germanic");
+    /// let (mut arena2, root2) = Arena::with_data("Romance");
+    ///
+    /// assert!(arena1.owns(root1));
+    /// assert!(!arena1.owns(root2));
+    /// // both roots occupy the same slot but tokens are not interchangeable
+    /// assert!(arena1.get(root2).is_none());
+    /// ```
+    pub fn owns(&self, token: Token) -> bool { self.allocator.owns(token) }
+
+    /// Checks whether every one of the given tokens corresponds to a node in
+    /// the arena. Returns true for an empty slice.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// assert!(arena.all_valid(&[root_token, germanic, romance]));
+    ///
+    /// arena.uproot(germanic);
+    /// assert!(!arena.all_valid(&[root_token, germanic, romance]));
+    /// ```
+    pub fn all_valid(&self, tokens: &[Token]) -> bool {
+        tokens.iter().all(|&token| self.get(token).is_some())
+    }
 
     /// Initializes arena and initializes a new tree with the given data at the
     /// root node.
@@ -71,17 +297,119 @@ impl<T> Arena<T> {
     /// assert_eq!(arena[root_token].data, 1);
     /// ```
     pub fn with_data(data: T) -> (Self, Token) {
-        let root_node = Node {
-            data,
-            parent: None,
-            previous_sibling: None,
-            token: Token { index: NonZeroUsize::new(1).unwrap() },
-            next_sibling: None,
-            first_child: None
+        let mut arena = Arena::new();
+        let root_token = arena.new_node(data);
+        (arena, root_token)
+    }
+
+    /// Initializes arena and initializes a new tree at the root node with the
+    /// data returned by the given function, if it succeeds. Nothing is
+    /// allocated if the function returns an error, which is passed along.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let (arena, root_token) = Arena::try_with_data(|| "42".parse::<i32>())
+    ///     .unwrap();
+    /// assert_eq!(arena[root_token].data, 42);
+    ///
+    /// let result = Arena::try_with_data(|| "forty-two".parse::<i32>());
+    /// assert!(result.is_err());
+    /// ```
+    pub fn try_with_data<E, F>(f: F) -> Result<(Self, Token), E>
+        where F: FnOnce() -> Result<T, E> {
+        f().map(Arena::with_data)
+    }
+
+    /// Builds an arena holding a single tree from a list of `(depth, data)`
+    /// pairs in pre-order, as produced by flattening a tree. Returns the arena
+    /// along with the token of the root.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `Err(Error::InvalidDepth)` if the list is empty, if the first
+    /// pair does not have a depth of 0, if any later pair has a depth of 0, or
+    /// if the depth ever increases by more than one from one pair to the next.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::{Arena, Error};
+    /// use atree::iter::TraversalOrder;
+    ///
+    /// let items = vec![(0, "Indo-European"), (1, "Germanic"), (2, "English"),
+    ///                  (1, "Romance")];
+    /// let (arena, root_token) = Arena::from_preorder_depths(items).unwrap();
+    /// let data: Vec<_> = root_token.subtree(&arena, TraversalOrder::Pre)
+    ///     .map(|x| x.data)
+    ///     .collect();
+    /// assert_eq!(&data[..], &["Indo-European", "Germanic", "English", "Romance"]);
+    ///
+    /// let items = vec![(0, "Indo-European"), (2, "English")];
+    /// assert!(matches!(Arena::from_preorder_depths(items),
+    ///                  Err(Error::InvalidDepth)));
+    /// ```
+    pub fn from_preorder_depths<I>(iter: I) -> Result<(Self, Token), Error>
+        where I: IntoIterator<Item = (usize, T)> {
+        let mut iter = iter.into_iter();
+        let (mut arena, root) = match iter.next() {
+            Some((0, data)) => Arena::with_data(data),
+            _ => return Err(Error::InvalidDepth)
+        };
+        // the ancestors of the next node, the root being at the bottom
+        let mut stack = vec![root];
+        for (depth, data) in iter {
+            if depth == 0 || depth > stack.len() {
+                return Err(Error::InvalidDepth)
+            }
+            stack.truncate(depth);
+            let parent = stack[depth - 1];
+            let token = parent.append(&mut arena, data);
+            stack.push(token);
+        }
+        Ok((arena, root))
+    }
+
+    /// Builds an arena holding a complete tree where every node has `arity`
+    /// children (except on the last levels) from items given in level order:
+    /// the first item is the root, the next `arity` items are its children,
+    /// and so on. This is the layout of an n-ary heap, where the parent of the
+    /// item at index `i` is at index `(i - 1) / arity`. Returns the arena
+    /// along with the token of the root.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if there are no items or if `arity` is 0.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    /// use atree::iter::TraversalOrder;
+    ///
+    /// let (arena, root_token) = Arena::balanced_from_slice(0..7, 2);
+    /// let data: Vec<_> = root_token.subtree(&arena, TraversalOrder::Pre)
+    ///     .map(|x| x.data)
+    ///     .collect();
+    /// assert_eq!(&data[..], &[0, 1, 3, 4, 2, 5, 6]);
+    /// ```
+    pub fn balanced_from_slice<I>(items: I, arity: usize) -> (Self, Token)
+        where I: IntoIterator<Item = T> {
+        if arity == 0 { panic!("The arity must be positive") }
+        let mut items = items.into_iter();
+        let (mut arena, root) = match items.next() {
+            Some(data) => Arena::with_data(data),
+            None => panic!("No items to build the tree from")
         };
-        let mut allocator = Allocator::new();
-        let root_token = allocator.insert(root_node);
-        (Arena { allocator }, root_token)
+        let mut tokens = vec![root];
+        for (i, data) in (1..).zip(items) {
+            let parent = tokens[(i - 1) / arity];
+            let token = parent.append(&mut arena, data);
+            tokens.push(token);
+        }
+        (arena, root)
     }
 
     /// Creates a new free node in the given arena.
@@ -106,7 +434,9 @@ impl<T> Arena<T> {
             previous_sibling: None,
             token,
             next_sibling: None,
-            first_child: None
+            first_child: None,
+            last_child: None,
+            stable_id: None
         };
         self.allocator.set(token, node);
         token
@@ -151,16 +481,134 @@ impl<T> Arena<T> {
         self.allocator.get_mut(indx)
     }
 
-    /// Sets data to node.
-    pub (crate) fn set(&mut self, indx: Token, node: Node<T>) {
-        if let Some(mut n) = self.allocator.set(indx, node) {
-            n.remove_descendants(self)
+    /// Gets a reference to the node in the given slot of the arena. Slots are
+    /// numbered from 1 onwards. Returns `None` if the slot is out of range or
+    /// does not hold a node. Prefer indexing with tokens where possible; this
+    /// is meant for code that deals with raw slot numbers (from serialized
+    /// data, for instance).
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Germanic";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let english = root_token.append(&mut arena, "English");
+    ///
+    /// assert_eq!(arena.get_by_index(1).unwrap().data, "Germanic");
+    /// assert_eq!(arena.get_by_index(2).unwrap().token(), english);
+    /// assert!(arena.get_by_index(0).is_none());
+    /// assert!(arena.get_by_index(100).is_none());
+    /// ```
+    pub fn get_by_index(&self, idx: usize) -> Option<&Node<T>> {
+        self.allocator.get_by_index(idx)
+    }
+
+    /// Gets a reference to the node found by following the given path of
+    /// (zero-based) child positions down from `root`. Returns `None` if
+    /// `root` is not in the arena or if any step of the path is out of range.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// root_token.append(&mut arena, "Romance");
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "English");
+    ///
+    /// assert_eq!(arena.get_path(root_token, &[1, 0]).unwrap().data, "English");
+    /// assert!(arena.get_path(root_token, &[0, 0]).is_none());
+    /// ```
+    pub fn get_path(&self, root: Token, indices: &[usize]) -> Option<&Node<T>> {
+        self.get(root)?;
+        let mut token = root;
+        for &i in indices {
+            token = token.nth_child(self, i)?;
+        }
+        self.get(token)
+    }
+
+    /// Returns the stable id of the given node, issuing one if the node does
+    /// not have one yet. Unlike tokens, stable ids are never reused: once the
+    /// node is removed, its stable id no longer resolves, even if its slot is
+    /// taken over by a new node. Use [`resolve_stable`] to get the token back.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    ///
+    /// let id = arena.stable_id(germanic);
+    /// assert_eq!(arena.stable_id(germanic), id);
+    /// assert_eq!(arena.resolve_stable(id), Some(germanic));
+    ///
+    /// // the slot is reused but the stable id does not resolve to the new node
+    /// arena.uproot(germanic);
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// assert_eq!(romance, germanic);
+    /// assert!(arena.resolve_stable(id).is_none());
+    /// ```
+    ///
+    /// [`resolve_stable`]: struct.Arena.html#method.resolve_stable
+    pub fn stable_id(&mut self, token: Token) -> StableId {
+        let next_stable_id = StableId(self.next_stable_id);
+        let node = match self.get_mut(token) {
+            None => panic!("Invalid token"),
+            Some(node) => node
+        };
+        match node.stable_id {
+            Some(id) => id,
+            None => {
+                node.stable_id = Some(next_stable_id);
+                self.stable_ids.insert(next_stable_id, token);
+                self.next_stable_id += 1;
+                next_stable_id
+            }
+        }
+    }
+
+    /// Returns the token of the node with the given stable id, or `None` if
+    /// the node has been removed. See [`stable_id`] for more.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let id = arena.stable_id(root_token);
+    /// assert_eq!(arena.resolve_stable(id), Some(root_token));
+    /// ```
+    ///
+    /// [`stable_id`]: struct.Arena.html#method.stable_id
+    pub fn resolve_stable(&self, id: StableId) -> Option<Token> {
+        let &token = self.stable_ids.get(&id)?;
+        match self.get(token)?.stable_id == Some(id) {
+            true => Some(token),
+            false => None
         }
     }
 
     /// Removes the given node from the arena and returns the tokens of its
-    /// children. Use [`uproot`] instead if you no longer need the descendants
-    /// of the node such that the freed memory could be reused.
+    /// children. The children (along with their descendants) take the place of
+    /// the removed node among its siblings. If the node is a root node, its
+    /// children become standalone trees in the arena instead. Use [`uproot`]
+    /// instead if you no longer need the descendants of the node such that the
+    /// freed memory could be reused.
+    ///
     /// # Panics:
     ///
     /// Panics if the token does not correspond to a node in the arena.
@@ -180,37 +628,26 @@ impl<T> Arena<T> {
     /// let scots = west.append(&mut arena, "Scots");
     /// let english = west.append(&mut arena, "English");
     ///
-    /// // detach the west branch from the main tree
+    /// // remove the west node from the tree
     /// let west_children = arena.remove(west);
     ///
-    /// // the west branch is gone from the original tree
+    /// // its children take its place in the tree
     /// let mut iter = root.subtree(&arena, TraversalOrder::Pre)
     ///     .map(|x| x.data);
     /// assert_eq!(iter.next(), Some("Indo-European"));
     /// assert_eq!(iter.next(), Some("Germanic"));
-    /// assert!(iter.next().is_none());
-    ///
-    /// // its children are still areound
-    /// let mut iter = west_children.iter().map(|&t| arena[t].data);
     /// assert_eq!(iter.next(), Some("Scots"));
     /// assert_eq!(iter.next(), Some("English"));
     /// assert!(iter.next().is_none());
+    ///
+    /// // the tokens of the children are returned
+    /// assert_eq!(&west_children[..], &[scots, english]);
     /// ```
     ///
     /// [`uproot`]: struct.Arena.html#method.uproot
     // cannot return an iterator since we need to drop the mutable borrow
     pub fn remove(&mut self, token: Token) -> Vec<Token> {
-        token.detach(self);
-        // The chidlren will remain siblings. Change in the future if this leads
-        // to problems.
-        for child in token.children_mut(self) {
-            child.parent = None;
-        }
-        // should not fail because children_mut checks the validity of token
-        let first_child = self[token].first_child;
-        self.allocator.remove(token);
-        let iter = ChildrenTokens { arena: self, node_token: first_child };
-        iter.collect()
+        token.remove_and_promote_children(self)
     }
 
     /// Removes the given node along with all its descendants. If you only
@@ -244,101 +681,785 @@ impl<T> Arena<T> {
     /// [`remove`]: struct.Arena.html#method.remove
     pub fn uproot(&mut self, token: Token) {
         token.remove_descendants(self);
-        match self.allocator.remove(token) {
-            None => panic!("Invalid token"),
-            Some(node) => match (node.parent, node.previous_sibling,
-                                 node.next_sibling) {
-                (Some(_), Some(otkn), Some(ytkn)) => {
-                    match self.get_mut(otkn) {
-                        Some(o) => o.next_sibling = Some(ytkn),
-                        None => panic!("Corrupt arena")
-                    }
-                    match self.get_mut(ytkn) {
-                        Some(y) => y.previous_sibling = Some(otkn),
-                        None => panic!("Corrupt arena")
-                    }
-                },
-                (Some(_), Some(otkn), None) => match self.get_mut(otkn) {
-                    Some(o) => o.next_sibling = None,
-                    None => panic!("Corrupt arena")
-                },
-                (Some(ptkn), None, Some(ytkn)) => match self.get_mut(ptkn) {
-                    Some(p) => p.first_child = Some(ytkn),
-                    None => panic!("Corrupt arena")
-                },
-                (Some(ptkn), None, None) => match self.get_mut(ptkn) {
-                    Some(p) => p.first_child = None,
-                    None => panic!("Corrupt arena")
-                },
-                (None, None, None) => (),  // empty tree
-                (None, None, Some(_))
-                    | (None, Some(_), None)
-                    | (None, Some(_), Some(_)) => panic!("Corrupt arena")
-            }
-        }
+        token.detach(self);
+        self.free(token);  // detach will have checked validity
     }
-}
 
-impl<T> Arena<T> where T: Clone {
-    /// Moves subtree with the root at the given node into its own arena. To
-    /// detach a given subtree root node from a tree into its own while
-    /// remaining in the same arena, use [`detach`] instead.
+    /// Creates new nodes with the given data and appends them to the given
+    /// node in order. The memory needed for the new nodes is reserved in one go
+    /// before any node is inserted. Returns the tokens of the new nodes.
     ///
     /// # Panics:
     ///
     /// Panics if the token does not correspond to a node in the arena.
     ///
     /// # Examples:
+    ///
     /// ```
     /// use atree::Arena;
-    /// use atree::iter::TraversalOrder;
     ///
-    /// let root_data = "a0";
-    /// let (mut arena1, root1) = Arena::with_data(root_data);
+    /// let root_data = "Germanic";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
     ///
-    /// let node1 = root1.append(&mut arena1, "a1");
-    /// let node2 = root1.append(&mut arena1, "b1");
-    /// let grandchild1 = node1.append(&mut arena1, "a2");
-    /// let grandchild2 = node2.append(&mut arena1, "b2");
+    /// let langs = vec!["English", "German", "Dutch"];
+    /// let tokens = arena.append_many_children(root_token, langs.into_iter());
     ///
-    /// // split tree
-    /// let (arena2, root2) = arena1.split_at(node2);
+    /// assert_eq!(tokens.len(), 3);
+    /// let children: Vec<_> = root_token.children(&arena).map(|x| x.data).collect();
+    /// assert_eq!(&["English", "German", "Dutch"], &children[..]);
+    /// ```
+    pub fn append_many_children<I>(&mut self, parent: Token, data: I)
+        -> Vec<Token> where I: ExactSizeIterator<Item = T> {
+        if self.get(parent).is_none() { panic!("Invalid token") }
+        let available = self.capacity() - self.node_count();
+        if data.len() > available {
+            self.allocator.reserve(data.len() - available);
+        }
+
+        let mut tokens: Vec<Token> = Vec::with_capacity(data.len());
+        for d in data {
+            let token = self.new_node(d);
+            match tokens.last() {
+                None => parent.link_append(self, token),
+                Some(&previous) => previous.link_after(self, token)
+            }
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    /// Swaps the data of two nodes while leaving the tree structure untouched.
+    /// Returns error if either token is invalid or if both tokens refer to the
+    /// same node.
     ///
-    /// let arena1_elt: Vec<_> = root1.subtree(&arena1, TraversalOrder::Pre)
-    ///     .map(|x| x.data).collect();
-    /// let arena2_elt: Vec<_> = root2.subtree(&arena2, TraversalOrder::Pre)
-    ///     .map(|x| x.data).collect();
+    /// # Examples:
     ///
-    /// assert_eq!(&["a0", "a1", "a2"], &arena1_elt[..]);
-    /// assert_eq!(&["b1", "b2"], &arena2_elt[..]);
     /// ```
+    /// use atree::{Arena, Error};
     ///
-    /// [`detach`]: struct.Token.html#method.detach
-    // TODO: could probably be optimized
-    pub fn split_at(&mut self, token: Token) -> (Self, Token) where T: Clone {
-        let root_data = match self.get(token) {
-            Some(node) => node.data.clone(),
-            None => panic!("Invalid token")
-        };
-        let (mut arena, root) = Arena::with_data(root_data);
-        for child_token in token.children_tokens(&self) {
-            arena.copy_and_append_subtree(root, self, child_token);
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    ///
+    /// arena.swap_data(germanic, english).unwrap();
+    /// assert_eq!(arena[germanic].data, "English");
+    /// assert_eq!(arena[english].data, "Germanic");
+    /// assert_eq!(arena[english].parent(), Some(germanic));
+    ///
+    /// assert_eq!(arena.swap_data(germanic, germanic), Err(Error::SameToken));
+    /// ```
+    pub fn swap_data(&mut self, a: Token, b: Token) -> Result<(), Error> {
+        if a == b {
+            return match self.get(a) {
+                None => Err(Error::InvalidToken),
+                Some(_) => Err(Error::SameToken)
+            }
+        }
+        match self.allocator.get_two_mut(a, b) {
+            None => Err(Error::InvalidToken),
+            Some((x, y)) => {
+                mem::swap(&mut x.data, &mut y.data);
+                Ok(())
+            }
         }
-        self.uproot(token);
-        (arena, root)
     }
 
-    /// Copies a sub-tree from one arena and append to the given node of another.
-    /// It does so by walking the tree and copying node by node to the target
-    /// arena.  Potentially expensive operation.
+    /// Swaps the positions of two nodes in their trees by relinking them, so
+    /// that each node (along with its descendants) takes the place of the
+    /// other under its parent and among its siblings. A root node trades
+    /// places with the other node by becoming free-standing. No data is moved
+    /// or cloned, and the nodes keep their tokens.
     ///
-    /// # Panics:
+    /// # Errors:
     ///
-    /// Panics if the token does not correspond to a node in the arena.
+    /// Returns `Err(Error::InvalidToken)` if either token does not correspond
+    /// to a node in the arena, `Err(Error::SameToken)` if both tokens refer to
+    /// the same node, and `Err(Error::WouldCycle)` if one node is an ancestor
+    /// of the other. The arena is left untouched in any of these cases.
     ///
     /// # Examples:
+    ///
     /// ```
-    /// use atree::Arena;
+    /// use atree::{Arena, Error};
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// let french = romance.append(&mut arena, "French");
+    ///
+    /// arena.swap_subtrees(english, french).unwrap();
+    /// assert_eq!(arena[english].parent(), Some(romance));
+    /// assert_eq!(arena[french].parent(), Some(germanic));
+    ///
+    /// assert_eq!(arena.swap_subtrees(germanic, french), Err(Error::WouldCycle));
+    /// ```
+    pub fn swap_subtrees(&mut self, a: Token, b: Token) -> Result<(), Error> {
+        let (parent_a, next_a) = match self.get(a) {
+            None => return Err(Error::InvalidToken),
+            Some(node) => (node.parent, node.next_sibling)
+        };
+        let (parent_b, next_b) = match self.get(b) {
+            None => return Err(Error::InvalidToken),
+            Some(node) => (node.parent, node.next_sibling)
+        };
+        if a == b { return Err(Error::SameToken) }
+        if a.is_ancestor_of(b, self) || b.is_ancestor_of(a, self) {
+            return Err(Error::WouldCycle)
+        }
+
+        // adjacent siblings only need the first one to hop over the second
+        if next_a == Some(b) {
+            a.detach(self);
+            b.link_after(self, a);
+            return Ok(())
+        }
+        if next_b == Some(a) {
+            b.detach(self);
+            a.link_after(self, b);
+            return Ok(())
+        }
+
+        // neither node is the next sibling of the other, so the slots can be
+        // refilled using the siblings and parents that stay in place
+        a.detach(self);
+        b.detach(self);
+        for &(token, parent, next_sibling) in &[(b, parent_a, next_a),
+                                                (a, parent_b, next_b)] {
+            match (next_sibling, parent) {
+                (Some(sibling), _) => sibling.link_before(self, token),
+                (None, Some(parent)) => parent.link_append(self, token),
+                (None, None) => ()
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates a cursor pointing at the given node. The cursor can be moved
+    /// around the tree the node belongs to.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// let french = romance.append(&mut arena, "French");
+    ///
+    /// let mut cursor = arena.cursor(root_token);
+    /// assert!(cursor.down());
+    /// assert_eq!(*cursor.data(), "Germanic");
+    /// assert!(!cursor.down());  // Germanic has no children
+    /// assert!(cursor.right());
+    /// assert!(cursor.down());
+    /// assert_eq!(cursor.token(), french);
+    /// ```
+    pub fn cursor(&self, token: Token) -> Cursor<'_, T> {
+        if self.get(token).is_none() { panic!("Invalid token") }
+        Cursor { arena: self, token }
+    }
+
+    /// Creates a cursor pointing at the given node that allows mutating the
+    /// data of the node under it.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = 1usize;
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let child = root_token.append(&mut arena, 2usize);
+    ///
+    /// let mut cursor = arena.cursor_mut(root_token);
+    /// assert!(cursor.down());
+    /// *cursor.data_mut() += 10;
+    /// assert_eq!(arena[child].data, 12);
+    /// ```
+    pub fn cursor_mut(&mut self, token: Token) -> CursorMut<'_, T> {
+        if self.get(token).is_none() { panic!("Invalid token") }
+        CursorMut { arena: self, token }
+    }
+
+    /// Creates a zipper focused on the given node. Unlike a cursor, edits made
+    /// through a zipper leave the arena untouched and produce a modified copy
+    /// of it instead.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    ///
+    /// let mut zipper = arena.focus(root_token);
+    /// assert!(zipper.down());
+    /// let edited = zipper.replace("Proto-Germanic");
+    /// assert_eq!(edited[germanic].data, "Proto-Germanic");
+    /// assert_eq!(arena[germanic].data, "Germanic");
+    /// ```
+    pub fn focus(&self, token: Token) -> Zipper<'_, T> {
+        if self.get(token).is_none() { panic!("Invalid token") }
+        Zipper { arena: self, token }
+    }
+
+    /// Collects statistics of the arena in one go.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// root_token.append(&mut arena, "Romance");
+    /// germanic.append(&mut arena, "English");
+    /// arena.new_node("Uralic");
+    ///
+    /// let stats = arena.stats();
+    /// assert_eq!(stats.node_count, 5);
+    /// assert_eq!(stats.root_count, 2);
+    /// assert_eq!(stats.max_height, 2);
+    /// assert_eq!(stats.leaf_count, 3);
+    /// ```
+    pub fn stats(&self) -> ArenaStats {
+        let mut roots = Vec::new();
+        let mut leaf_count = 0;
+        for node in self.allocator.iter() {
+            if node.parent.is_none() { roots.push(node.token) }
+            if node.is_leaf() { leaf_count += 1 }
+        }
+        let root_count = roots.len();
+
+        // walk all the trees level by level at once
+        let mut max_height = 0;
+        let mut curr_level = roots;
+        loop {
+            let next_level: Vec<Token> = curr_level.iter()
+                .flat_map(|token| token.children_tokens(self))
+                .collect();
+            if next_level.is_empty() { break }
+            max_height += 1;
+            curr_level = next_level;
+        }
+
+        ArenaStats {
+            node_count: self.node_count(),
+            capacity: self.capacity(),
+            root_count,
+            max_height,
+            leaf_count
+        }
+    }
+
+    /// Verifies that the arena holds a well formed forest: every link of every
+    /// node refers to a node in the arena, sibling links agree with each other,
+    /// children point back to their parents, and every node can be reached
+    /// from a root without running into a cycle. Returns the first violation
+    /// found. This is meant for testing and debugging since a well behaved
+    /// program never corrupts an arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "English");
+    /// arena.remove(germanic);
+    ///
+    /// assert!(arena.check_integrity().is_ok());
+    /// ```
+    pub fn check_integrity(&self) -> Result<(), IntegrityError> {
+        let resolve = |node: &Node<T>, link: Option<Token>| match link {
+            None => Ok(None),
+            Some(token) => match self.get(token) {
+                None => Err(IntegrityError::DanglingLink(node.token)),
+                Some(other) => Ok(Some(other))
+            }
+        };
+
+        let mut roots = Vec::new();
+        for node in self.allocator.iter() {
+            let token = node.token;
+            match self.get(token) {
+                Some(n) if std::ptr::eq(n, node) => (),
+                _ => return Err(IntegrityError::TokenMismatch(token))
+            }
+
+            if let Some(previous) = resolve(node, node.previous_sibling)? {
+                if previous.next_sibling != Some(token) {
+                    return Err(IntegrityError::SiblingMismatch(token))
+                }
+                if previous.parent != node.parent {
+                    return Err(IntegrityError::ParentMismatch(token))
+                }
+            }
+            if let Some(next) = resolve(node, node.next_sibling)? {
+                if next.previous_sibling != Some(token) {
+                    return Err(IntegrityError::SiblingMismatch(token))
+                }
+                if next.parent != node.parent {
+                    return Err(IntegrityError::ParentMismatch(token))
+                }
+            }
+
+            match (resolve(node, node.first_child)?,
+                   resolve(node, node.last_child)?) {
+                (None, None) => (),
+                (Some(first), Some(last)) => {
+                    if first.parent != Some(token) || last.parent != Some(token) {
+                        return Err(IntegrityError::ParentMismatch(token))
+                    }
+                    if first.previous_sibling.is_some()
+                        || last.next_sibling.is_some() {
+                        return Err(IntegrityError::ChildMismatch(token))
+                    }
+                },
+                _ => return Err(IntegrityError::ChildMismatch(token))
+            }
+
+            match resolve(node, node.parent)? {
+                None => if node.previous_sibling.is_none() { roots.push(token) },
+                Some(parent) => if parent.first_child.is_none() {
+                    return Err(IntegrityError::ParentMismatch(token))
+                }
+            }
+        }
+
+        // every node must be reached exactly once by walking down from roots
+        let mut visited = HashSet::new();
+        let mut stack = roots;
+        while let Some(token) = stack.pop() {
+            if !visited.insert(token) { return Err(IntegrityError::Cycle(token)) }
+            let node = &self[token];  // links have been checked
+            stack.extend(node.next_sibling);
+            stack.extend(node.first_child);
+        }
+        match self.allocator.iter().find(|node| !visited.contains(&node.token)) {
+            None => Ok(()),
+            Some(node) => Err(IntegrityError::Cycle(node.token))
+        }
+    }
+
+    /// Moves the subtree with the root at the given node into another arena as
+    /// a new standalone tree. Unlike [`split_at`] and
+    /// [`copy_and_append_subtree`], the data is moved rather than cloned.
+    /// Returns the token of the root of the subtree in the other arena.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    /// ```
+    /// use atree::Arena;
+    /// use atree::iter::TraversalOrder;
+    ///
+    /// let (mut arena1, root1) = Arena::with_data("Indo-European");
+    /// let germanic = root1.append(&mut arena1, "Germanic");
+    /// germanic.append(&mut arena1, "English");
+    /// germanic.append(&mut arena1, "Dutch");
+    ///
+    /// let (mut arena2, root2) = Arena::with_data("Uralic");
+    /// let moved = arena1.move_subtree_into(germanic, &mut arena2);
+    ///
+    /// assert_eq!(arena1.node_count(), 1);
+    /// assert_eq!(arena2.node_count(), 4);
+    /// let data: Vec<_> = moved.subtree(&arena2, TraversalOrder::Pre)
+    ///     .map(|x| x.data)
+    ///     .collect();
+    /// assert_eq!(&["Germanic", "English", "Dutch"], &data[..]);
+    /// ```
+    ///
+    /// [`split_at`]: struct.Arena.html#method.split_at
+    /// [`copy_and_append_subtree`]: struct.Arena.html#method.copy_and_append_subtree
+    pub fn move_subtree_into(&mut self, token: Token, dest: &mut Arena<T>)
+        -> Token {
+        token.detach(self);
+        let tokens: Vec<Token> = token.subtree_tokens(self, TraversalOrder::Pre)
+            .collect();
+        let mut index_map: HashMap<Token, Token> = HashMap::new();
+        for t in tokens {
+            let node = match self.free(t) {
+                Some(node) => node,
+                None => panic!("Corrupt arena")
+            };
+            // parents always come before their children in pre-order
+            let new_token = match node.parent {
+                None => dest.new_node(node.data),
+                Some(parent) => index_map[&parent].append(dest, node.data)
+            };
+            index_map.insert(t, new_token);
+        }
+        index_map[&token]
+    }
+
+    /// Replaces the data of every node in the arena with the output of the
+    /// given function. Nodes for which the function returns `None` are removed
+    /// along with their descendants. The trees are processed from the top down
+    /// so the function is never called on the descendants of removed nodes.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    /// use atree::iter::TraversalOrder;
+    ///
+    /// let root_data = 1usize;
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let first_child = root_token.append(&mut arena, 2usize);
+    /// root_token.append(&mut arena, 3usize);
+    /// first_child.append(&mut arena, 4usize);
+    ///
+    /// // drop the even numbers and square the rest
+    /// arena.filter_map_data(|&x| match x % 2 {
+    ///     0 => None,
+    ///     _ => Some(x * x)
+    /// });
+    ///
+    /// let data: Vec<_> = root_token.subtree(&arena, TraversalOrder::Pre)
+    ///     .map(|x| x.data)
+    ///     .collect();
+    /// assert_eq!(&[1, 9], &data[..]);
+    /// ```
+    pub fn filter_map_data<F>(&mut self, mut f: F)
+        where F: FnMut(&T) -> Option<T> {
+        let mut stack: Vec<Token> = self.allocator.iter()
+            .filter(|node| node.parent.is_none())
+            .map(|node| node.token)
+            .collect();
+        while let Some(token) = stack.pop() {
+            match f(&self[token].data) {
+                None => self.uproot(token),
+                Some(data) => {
+                    self[token].data = data;
+                    let children: Vec<Token> = token.children_tokens(self)
+                        .collect();
+                    stack.extend(children.into_iter().rev());
+                }
+            }
+        }
+    }
+
+    /// Returns the node that comes after `from` in a pre-order traversal of
+    /// the subtree rooted at `root`, or `None` if `from` is the last node of
+    /// the traversal. A traversal starts at `root` itself. `from` is expected
+    /// to be `root` or one of its descendants.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the tokens do not correspond to nodes in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let french = romance.append(&mut arena, "French");
+    /// germanic.append(&mut arena, "English");
+    ///
+    /// let mut walk = vec![root_token];
+    /// let mut token = root_token;
+    /// while let Some(next) = arena.step_preorder(token, root_token) {
+    ///     walk.push(next);
+    ///     token = next;
+    /// }
+    /// let data: Vec<_> = walk.iter().map(|&t| arena[t].data).collect();
+    /// assert_eq!(&data[..], &["Indo-European", "Romance", "French",
+    ///                         "Germanic", "English"]);
+    ///
+    /// // the walk stays within the subtree
+    /// assert!(arena.step_preorder(french, romance).is_none());
+    /// ```
+    pub fn step_preorder(&self, from: Token, root: Token) -> Option<Token> {
+        if self.get(root).is_none() { panic!("Invalid token") }
+        preorder_next(from, root, Branch::Child, self).0
+    }
+
+    /// Returns the node that comes after `from` in a post-order traversal of
+    /// the subtree rooted at `root`, or `None` if `from` is `root` (the last
+    /// node of the traversal). A traversal starts at the left-most leaf of the
+    /// subtree, which can be reached by following the first children down from
+    /// `root`. `from` is expected to be `root` or one of its descendants.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the tokens do not correspond to nodes in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let french = romance.append(&mut arena, "French");
+    /// germanic.append(&mut arena, "English");
+    ///
+    /// let mut walk = vec![french];
+    /// let mut token = french;
+    /// while let Some(next) = arena.step_postorder(token, root_token) {
+    ///     walk.push(next);
+    ///     token = next;
+    /// }
+    /// let data: Vec<_> = walk.iter().map(|&t| arena[t].data).collect();
+    /// assert_eq!(&data[..], &["French", "Romance", "English", "Germanic",
+    ///                         "Indo-European"]);
+    /// ```
+    pub fn step_postorder(&self, from: Token, root: Token) -> Option<Token> {
+        if self.get(root).is_none() { panic!("Invalid token") }
+        match from == root {
+            true => None,
+            false => postorder_next(from, root, Branch::Sibling, self).0
+        }
+    }
+
+    /// Detaches the subtree rooted at the given node into its own tree (see
+    /// [`detach`]) and returns the tokens of the roots of both resulting trees:
+    /// the root of the tree the node used to belong to, followed by the node
+    /// itself. If the node is already a root, both tokens are the node itself.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let west = germanic.append(&mut arena, "West");
+    /// west.append(&mut arena, "English");
+    ///
+    /// let (original, detached) = arena.detach_returning_roots(west);
+    /// assert_eq!((original, detached), (root_token, west));
+    /// assert!(arena[west].parent().is_none());
+    /// assert!(germanic.is_leaf(&arena));
+    /// ```
+    ///
+    /// [`detach`]: struct.Token.html#method.detach
+    pub fn detach_returning_roots(&mut self, token: Token) -> (Token, Token) {
+        let parent = match self.get(token) {
+            None => panic!("Invalid token"),
+            Some(node) => node.parent
+        };
+        token.detach(self);
+        let original_root = match parent {
+            None => token,
+            Some(parent) => parent.root(self)
+        };
+        (original_root, token)
+    }
+
+    /// Moves the given nodes (along with their descendants) under `parent` as
+    /// its last children, in the given order. All the nodes are checked before
+    /// anything is moved, so the arena is left untouched on error.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `Err(Error::InvalidToken)` if any of the tokens does not
+    /// correspond to a node in the arena, `Err(Error::SameToken)` if a node is
+    /// listed more than once, and `Err(Error::WouldCycle)` if `parent` is one of
+    /// the nodes or one of their descendants.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::{Arena, Error};
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = root_token.append(&mut arena, "English");
+    /// let german = root_token.append(&mut arena, "German");
+    ///
+    /// arena.reparent_many(germanic, &[german, english]).unwrap();
+    /// let children: Vec<_> = germanic.children_tokens(&arena).collect();
+    /// assert_eq!(&children[..], &[german, english]);
+    ///
+    /// assert_eq!(arena.reparent_many(english, &[germanic]),
+    ///            Err(Error::WouldCycle));
+    /// ```
+    pub fn reparent_many(&mut self, parent: Token, children: &[Token])
+        -> Result<(), Error> {
+        if self.get(parent).is_none() { return Err(Error::InvalidToken) }
+        let ancestors: HashSet<Token> = parent.ancestors_inclusive_tokens(self)
+            .collect();
+        let mut seen = HashSet::new();
+        for &child in children {
+            if self.get(child).is_none() { return Err(Error::InvalidToken) }
+            if !seen.insert(child) { return Err(Error::SameToken) }
+            if ancestors.contains(&child) { return Err(Error::WouldCycle) }
+        }
+
+        for &child in children {
+            child.detach(self);
+            parent.link_append(self, child);
+        }
+        Ok(())
+    }
+
+    /// Returns a reference to the data of the root node if the arena holds
+    /// exactly one tree. Returns `None` if the arena is empty or holds more
+    /// than one tree.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// root_token.append(&mut arena, "Germanic");
+    /// assert_eq!(arena.root_data(), Some(&"Indo-European"));
+    ///
+    /// arena.new_node("Uralic");
+    /// assert!(arena.root_data().is_none());
+    /// ```
+    pub fn root_data(&self) -> Option<&T> {
+        let mut roots = self.allocator.iter()
+            .filter(|node| node.parent.is_none());
+        match (roots.next(), roots.next()) {
+            (Some(root), None) => Some(&root.data),
+            _ => None
+        }
+    }
+
+    /// Uproots the first node (in slot order) whose data satisfies the given
+    /// predicate, along with its descendants, and returns its former token.
+    /// Returns `None` if no node matches.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "English");
+    ///
+    /// assert_eq!(arena.remove_first_matching(|&x| x == "Germanic"),
+    ///            Some(germanic));
+    /// assert_eq!(arena.node_count(), 1);
+    /// assert!(arena.remove_first_matching(|&x| x == "Slavic").is_none());
+    /// ```
+    pub fn remove_first_matching<F>(&mut self, mut f: F) -> Option<Token>
+        where F: FnMut(&T) -> bool {
+        let token = self.allocator.iter()
+            .find(|node| f(&node.data))
+            .map(|node| node.token)?;
+        self.uproot(token);
+        Some(token)
+    }
+
+    /// Frees the slot of the given node and forgets its stable id (if any).
+    /// The node is expected to have been unlinked from its tree already.
+    pub (crate) fn free(&mut self, token: Token) -> Option<Node<T>> {
+        let node = self.allocator.remove(token)?;
+        if let Some(id) = node.stable_id {
+            self.stable_ids.remove(&id);
+        }
+        Some(node)
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self { Arena::new() }
+}
+
+impl<T> Arena<T> where T: Clone {
+    /// Moves subtree with the root at the given node into its own arena. To
+    /// detach a given subtree root node from a tree into its own while
+    /// remaining in the same arena, use [`detach`] instead.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    /// ```
+    /// use atree::Arena;
+    /// use atree::iter::TraversalOrder;
+    ///
+    /// let root_data = "a0";
+    /// let (mut arena1, root1) = Arena::with_data(root_data);
+    ///
+    /// let node1 = root1.append(&mut arena1, "a1");
+    /// let node2 = root1.append(&mut arena1, "b1");
+    /// let grandchild1 = node1.append(&mut arena1, "a2");
+    /// let grandchild2 = node2.append(&mut arena1, "b2");
+    ///
+    /// // split tree
+    /// let (arena2, root2) = arena1.split_at(node2);
+    ///
+    /// let arena1_elt: Vec<_> = root1.subtree(&arena1, TraversalOrder::Pre)
+    ///     .map(|x| x.data).collect();
+    /// let arena2_elt: Vec<_> = root2.subtree(&arena2, TraversalOrder::Pre)
+    ///     .map(|x| x.data).collect();
+    ///
+    /// assert_eq!(&["a0", "a1", "a2"], &arena1_elt[..]);
+    /// assert_eq!(&["b1", "b2"], &arena2_elt[..]);
+    /// ```
+    ///
+    /// [`detach`]: struct.Token.html#method.detach
+    // TODO: could probably be optimized
+    pub fn split_at(&mut self, token: Token) -> (Self, Token) where T: Clone {
+        let root_data = match self.get(token) {
+            Some(node) => node.data.clone(),
+            None => panic!("Invalid token")
+        };
+        let (mut arena, root) = Arena::with_data(root_data);
+        for child_token in token.children_tokens(self) {
+            arena.copy_and_append_subtree(root, self, child_token);
+        }
+        self.uproot(token);
+        (arena, root)
+    }
+
+    /// Copies a sub-tree from one arena and append to the given node of another.
+    /// It does so by walking the tree and copying node by node to the target
+    /// arena.  Potentially expensive operation.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    /// ```
+    /// use atree::Arena;
     /// use atree::iter::TraversalOrder;
     ///
     /// let root_data = "John";
@@ -347,7 +1468,8 @@ impl<T> Arena<T> where T: Clone {
     /// let node1 = root_token.append(&mut arena1, "Juan");
     /// let node2 = root_token.append(&mut arena1, "Giovanni");
     /// let grandchild1 = node1.append(&mut arena1, "Ivan");
-    /// let grandchild2 = node2.append(&mut arena1, "Johann");
+    /// let grandchild2 = node1.append(&mut arena1, "Jean");
+    /// let grandchild3 = node2.append(&mut arena1, "Johann");
     ///
     /// // new arena
     /// let mut arena2 = arena1.clone();
@@ -356,58 +1478,370 @@ impl<T> Arena<T> where T: Clone {
     /// arena1.copy_and_append_subtree(node2, &arena2, node1);
     /// let mut subtree = node2.subtree(&arena1, TraversalOrder::Pre);
     ///
-    /// assert_eq!(subtree.next().unwrap().data, "Giovanni");
-    /// assert_eq!(subtree.next().unwrap().data, "Johann");
-    /// assert_eq!(subtree.next().unwrap().data, "Juan");
-    /// assert_eq!(subtree.next().unwrap().data, "Ivan");
-    /// assert!(subtree.next().is_none());
+    /// assert_eq!(subtree.next().unwrap().data, "Giovanni");
+    /// assert_eq!(subtree.next().unwrap().data, "Johann");
+    /// assert_eq!(subtree.next().unwrap().data, "Juan");
+    /// assert_eq!(subtree.next().unwrap().data, "Ivan");
+    /// assert_eq!(subtree.next().unwrap().data, "Jean");
+    /// assert!(subtree.next().is_none());
+    /// ```
+    pub fn copy_and_append_subtree(&mut self, self_token: Token,
+                                   other_tree: &Arena<T>, other_token: Token) {
+        match other_tree.get(other_token) {
+            None => panic!("Invalid token"),
+            Some(node) => {
+                let new_subtree_root = self_token.append(self, node.data.clone());
+                let mut index_map: HashMap<Token, Token> = HashMap::new();
+                index_map.insert(other_token, new_subtree_root);
+
+                let mut stack = vec![other_token];
+                let mut branch = Branch::Child;
+
+                loop {
+                    let &token = stack.last().unwrap(); // never fails
+                    let node = &other_tree[token];  // already checked
+                    match branch {
+                        Branch::None => (),  // unreachable
+                        Branch::Child => match node.first_child {
+                            None => branch = Branch::Sibling,
+                            Some(child) => {
+                                let child_data = match other_tree.get(child) {
+                                    Some(node) => node.data.clone(),
+                                    None => panic!("Corrupt arena")
+                                };
+                                let new_parent = index_map[&token];
+                                let new_child_token =
+                                    new_parent.append(self, child_data);
+                                index_map.insert(child, new_child_token);
+                                stack.push(child);
+                            }
+                        },
+                        Branch::Sibling => match Some(other_token) == stack.pop() {
+                            true => break,
+                            false => match (node.next_sibling, node.parent) {
+                                (None, _) => (),
+                                (Some(sibling), Some(parent)) => {
+                                    let sibling_data = match other_tree.get(sibling) {
+                                        Some(node) => node.data.clone(),
+                                        None => panic!("Corrupt arena")
+                                    };
+                                    let new_parent = index_map[&parent];
+                                    let new_sibling_token =
+                                        new_parent.append(self, sibling_data);
+                                    index_map.insert(sibling, new_sibling_token);
+                                    stack.push(sibling);
+                                    branch = Branch::Child;
+                                },
+                                (Some(_), None) => panic!("Corrupt arena")
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replaces the descendants of the given node with a copy of a subtree
+    /// from another arena. The node itself keeps its data and its position in
+    /// the tree. The copied subtree is appended to the node in the same way as
+    /// [`copy_and_append_subtree`].
+    ///
+    /// # Panics:
+    ///
+    /// Panics if either token does not correspond to a node in its arena.
+    ///
+    /// # Examples:
+    /// ```
+    /// use atree::Arena;
+    /// use atree::iter::TraversalOrder;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena1, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena1, "Germanic");
+    /// germanic.append(&mut arena1, "English");
+    ///
+    /// let (mut arena2, west) = Arena::with_data("West");
+    /// west.append(&mut arena2, "Scots");
+    ///
+    /// arena1.replace_subtree_from(germanic, &arena2, west);
+    /// let subtree: Vec<_> = root_token.subtree(&arena1, TraversalOrder::Pre)
+    ///     .map(|x| x.data)
+    ///     .collect();
+    /// assert_eq!(&subtree[..], &["Indo-European", "Germanic", "West", "Scots"]);
+    /// ```
+    ///
+    /// [`copy_and_append_subtree`]: struct.Arena.html#method.copy_and_append_subtree
+    pub fn replace_subtree_from(&mut self, target: Token, source: &Arena<T>,
+                                source_root: Token) {
+        if source.get(source_root).is_none() { panic!("Invalid token") }
+        target.remove_descendants(self);
+        self.copy_and_append_subtree(target, source, source_root);
+    }
+}
+
+impl<T> Arena<T> where T: Default + PartialEq {
+    /// Removes every leaf whose data equals `T::default()`. Parents left
+    /// without children by the removal are removed in turn if their data is
+    /// the default as well, until no such leaf remains.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let (mut arena, root_token) = Arena::with_data(1usize);
+    /// let child = root_token.append(&mut arena, 0);
+    /// child.append(&mut arena, 0);
+    /// let other_child = root_token.append(&mut arena, 0);
+    /// other_child.append(&mut arena, 2);
+    ///
+    /// arena.remove_default_leaves();
+    /// assert_eq!(arena.node_count(), 3);
+    /// assert!(arena.get(child).is_none());
+    /// ```
+    pub fn remove_default_leaves(&mut self) {
+        let default = T::default();
+        let roots: Vec<Token> = self.allocator.iter()
+            .filter(|node| node.parent.is_none())
+            .map(|node| node.token)
+            .collect();
+        for root in roots {
+            // children come before their parents in post-order, so parents
+            // that lose all their children are looked at afterwards
+            let tokens: Vec<Token> = root.subtree_tokens(self, TraversalOrder::Post)
+                .collect();
+            for token in tokens {
+                if token.is_leaf(self) && self[token].data == default {
+                    self.uproot(token);
+                }
+            }
+        }
+    }
+}
+
+impl<T> Arena<T> where T: PartialEq + Clone {
+    /// Compares the subtree of `root_a` in this arena with the subtree of
+    /// `root_b` in `other`, matching nodes by their positions among their
+    /// siblings. Returns the differences in pre-order. Every node of a subtree
+    /// that is only in one of the trees is reported on its own.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the tokens do not correspond to nodes in their arenas.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::{Arena, TreeDiff};
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut old_arena, old_root) = Arena::with_data(root_data);
+    /// old_root.append(&mut old_arena, "Romance");
+    ///
+    /// let (mut new_arena, new_root) = Arena::with_data(root_data);
+    /// new_root.append(&mut new_arena, "Germanic");
+    /// new_root.append(&mut new_arena, "Slavic");
+    ///
+    /// let diffs = old_arena.diff(&new_arena, old_root, new_root);
+    /// assert_eq!(diffs, vec![
+    ///     TreeDiff::Changed { path: vec![0], old: "Romance", new: "Germanic" },
+    ///     TreeDiff::Added { path: vec![1], data: "Slavic" }
+    /// ]);
+    /// ```
+    pub fn diff(&self, other: &Arena<T>, root_a: Token, root_b: Token)
+        -> Vec<TreeDiff<T>> {
+        let mut diffs = Vec::new();
+        let mut stack = vec![(Some(root_a), Some(root_b), Vec::new())];
+        while let Some((a, b, path)) = stack.pop() {
+            let node_a = a.map(|token| match self.get(token) {
+                Some(node) => node,
+                None => panic!("Invalid token")
+            });
+            let node_b = b.map(|token| match other.get(token) {
+                Some(node) => node,
+                None => panic!("Invalid token")
+            });
+            let children_a: Vec<Token> = match node_a {
+                Some(node) => node.children_tokens(self).collect(),
+                None => Vec::new()
+            };
+            let children_b: Vec<Token> = match node_b {
+                Some(node) => node.children_tokens(other).collect(),
+                None => Vec::new()
+            };
+            // push in reverse so that the children are visited in order
+            for i in (0..children_a.len().max(children_b.len())).rev() {
+                let mut child_path = path.clone();
+                child_path.push(i);
+                stack.push((children_a.get(i).copied(),
+                            children_b.get(i).copied(),
+                            child_path));
+            }
+            match (node_a, node_b) {
+                (Some(x), Some(y)) => if x.data != y.data {
+                    diffs.push(TreeDiff::Changed {
+                        path,
+                        old: x.data.clone(),
+                        new: y.data.clone()
+                    })
+                },
+                (Some(x), None) => diffs.push(TreeDiff::Removed {
+                    path,
+                    data: x.data.clone()
+                }),
+                (None, Some(y)) => diffs.push(TreeDiff::Added {
+                    path,
+                    data: y.data.clone()
+                }),
+                (None, None) => panic!("Corrupt arena")
+            }
+        }
+        diffs
+    }
+}
+
+impl<T> Arena<T> where T: Eq + Hash {
+    /// Assigns every node in the arena an id such that two nodes get the same
+    /// id if and only if their subtrees are identical, both in shape and in
+    /// data. Ids are computed bottom-up from the data of each node and the ids
+    /// of its children, and are only meaningful within one call.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    /// let other_germanic = root_token.append(&mut arena, "Germanic");
+    /// let other_english = other_germanic.append(&mut arena, "English");
+    ///
+    /// let ids = arena.canonicalize();
+    /// assert_eq!(ids[&germanic], ids[&other_germanic]);
+    /// assert_eq!(ids[&english], ids[&other_english]);
+    /// assert_ne!(ids[&germanic], ids[&english]);
+    /// ```
+    pub fn canonicalize(&self) -> HashMap<Token, u32> {
+        let mut ids = HashMap::new();
+        let mut interned: HashMap<(&T, Vec<u32>), u32> = HashMap::new();
+        let roots = self.allocator.iter().filter(|node| node.parent.is_none());
+        for root in roots {
+            for node in root.token.subtree(self, TraversalOrder::Post) {
+                // children are visited before their parents in post-order
+                let children: Vec<u32> = node.children_tokens(self)
+                    .map(|child| ids[&child])
+                    .collect();
+                let next_id = interned.len() as u32;
+                let id = *interned.entry((&node.data, children))
+                    .or_insert(next_id);
+                ids.insert(node.token, id);
+            }
+        }
+        ids
+    }
+
+    /// Finds the subtrees that occur more than once in the arena, in shape and
+    /// in data. Returns groups of tokens of the roots of identical subtrees,
+    /// each group holding at least two tokens. Subtrees nested within a larger
+    /// duplicated subtree are not reported on their own. Groups and the tokens
+    /// within them are in the order the nodes are laid out in memory.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "English");
+    /// let other_germanic = root_token.append(&mut arena, "Germanic");
+    /// other_germanic.append(&mut arena, "English");
+    /// root_token.append(&mut arena, "Romance");
+    ///
+    /// assert_eq!(arena.dedup_report(), vec![vec![germanic, other_germanic]]);
     /// ```
-    pub fn copy_and_append_subtree(&mut self, self_token: Token,
-                                   other_tree: &Arena<T>, other_token: Token) {
-        match other_tree.get(other_token) {
-            None => panic!("Invalid token"),
-            Some(node) => {
-                let new_subtree_root = self_token.append(self, node.data.clone());
-                let mut index_map: HashMap<Token, Token> = HashMap::new();
-                index_map.insert(other_token, new_subtree_root);
+    pub fn dedup_report(&self) -> Vec<Vec<Token>> {
+        let ids = self.canonicalize();
+        let mut counts: HashMap<u32, usize> = HashMap::new();
+        for id in ids.values() {
+            *counts.entry(*id).or_insert(0) += 1;
+        }
+        let is_duplicate = |token: &Token| counts[&ids[token]] > 1;
 
-                let mut stack = vec![other_token];
-                let mut branch = Branch::Child;
+        let mut groups: Vec<Vec<Token>> = Vec::new();
+        let mut group_indices: HashMap<u32, usize> = HashMap::new();
+        for node in self.allocator.iter() {
+            let nested = match node.parent {
+                Some(parent) => is_duplicate(&parent),
+                None => false
+            };
+            if nested || !is_duplicate(&node.token) { continue }
+            let id = ids[&node.token];
+            let next_index = groups.len();
+            let index = *group_indices.entry(id).or_insert(next_index);
+            match groups.get_mut(index) {
+                Some(group) => group.push(node.token),
+                None => groups.push(vec![node.token])
+            }
+        }
+        // the other copies of a subtree may all be nested in duplicates
+        groups.retain(|group| group.len() > 1);
+        groups
+    }
+}
 
-                loop {
-                    let &token = stack.last().unwrap(); // never fails
-                    let node = &other_tree[token];  // already checked
-                    match branch {
-                        Branch::None => (),  // unreachable
-                        Branch::Child => match node.first_child {
-                            None => branch = Branch::Sibling,
-                            Some(child) => {
-                                let child_data = match other_tree.get(child) {
-                                    Some(node) => node.data.clone(),
-                                    None => panic!("Corrupt arena")
-                                };
-                                let new_parent = index_map[&token];
-                                let new_child_token =
-                                    new_parent.append(self, child_data);
-                                index_map.insert(child, new_child_token);
-                                stack.push(child);
-                            }
-                        },
-                        Branch::Sibling => match Some(other_token) == stack.pop() {
-                            true => break,
-                            false => match node.next_sibling {
-                                None => (),
-                                Some(sibling) => {
-                                    stack.push(sibling);
-                                    branch = Branch::Child;
-                                }
-                            }
-                        }
-                    }
-                }
+/// Compares two arenas as forests. Two arenas are equal if they hold the same
+/// trees, where trees are compared by shape and data. The order in which the
+/// trees appear in the arenas and the tokens of the nodes are irrelevant.
+impl<T> PartialEq for Arena<T> where T: PartialEq {
+    fn eq(&self, other: &Self) -> bool {
+        if self.node_count() != other.node_count() { return false }
+        let roots = |arena: &Arena<T>| -> Vec<Token> {
+            arena.allocator.iter()
+                .filter(|node| node.parent.is_none())
+                .map(|node| node.token)
+                .collect()
+        };
+        let self_roots = roots(self);
+        let mut other_roots = roots(other);
+        if self_roots.len() != other_roots.len() { return false }
+
+        for root in self_roots {
+            let matched = other_roots.iter()
+                .position(|&r| subtree_eq(self, root, other, r));
+            match matched {
+                None => return false,
+                Some(i) => { other_roots.swap_remove(i); }
+            }
+        }
+        true
+    }
+}
+
+impl<T> Eq for Arena<T> where T: Eq {}
+
+/// Checks whether two subtrees (possibly from different arenas) have the same
+/// shape and data.
+fn subtree_eq<T>(arena: &Arena<T>, token: Token,
+                 other_arena: &Arena<T>, other_token: Token) -> bool
+    where T: PartialEq {
+    let mut stack = vec![(token, other_token)];
+    while let Some((a, b)) = stack.pop() {
+        if arena[a].data != other_arena[b].data { return false }
+        let mut children = a.children_tokens(arena);
+        let mut other_children = b.children_tokens(other_arena);
+        loop {
+            match (children.next(), other_children.next()) {
+                (None, None) => break,
+                (Some(x), Some(y)) => stack.push((x, y)),
+                _ => return false
             }
         }
     }
+    true
 }
 
 impl<T> Index<Token> for Arena<T> {
@@ -428,3 +1862,1132 @@ impl<T> IndexMut<Token> for Arena<T> {
         }
     }
 }
+
+/// Iterates over all the nodes in the arena regardless of the trees they
+/// belong to. Nodes are visited in the order they are laid out in memory.
+///
+/// # Examples:
+///
+/// ```
+/// use atree::Arena;
+///
+/// let root_data = 1usize;
+/// let (mut arena, root_token) = Arena::with_data(root_data);
+/// root_token.append(&mut arena, 2usize);
+/// arena.new_node(3usize);
+///
+/// let mut sum = 0;
+/// for node in &arena {
+///     sum += node.data;
+/// }
+/// assert_eq!(sum, 6);
+/// ```
+impl<'a, T> IntoIterator for &'a Arena<T> {
+    type Item = &'a Node<T>;
+    type IntoIter = Nodes<'a, T>;
+    fn into_iter(self) -> Nodes<'a, T> { Nodes { arena: self, index: 0 } }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn eq_different_histories() {
+        let (mut arena1, root1) = Arena::with_data("Indo-European");
+        let germanic = root1.append(&mut arena1, "Germanic");
+        root1.append(&mut arena1, "Romance");
+        germanic.append(&mut arena1, "English");
+        arena1.new_node("Uralic");
+
+        // same forest built in a different order and with some churn
+        let mut arena2 = Arena::default();
+        let uralic = arena2.new_node("Uralic");
+        let scratch = uralic.append(&mut arena2, "Scratch");
+        arena2.uproot(scratch);
+        let root2 = arena2.new_node("Indo-European");
+        let romance = root2.append(&mut arena2, "Romance");
+        let germanic = romance.insert_before(&mut arena2, "Germanic");
+        germanic.append(&mut arena2, "English");
+
+        assert!(arena1 == arena2);
+        assert!(arena2 == arena1);
+    }
+
+    #[test]
+    fn ne_structure_or_data() {
+        let (mut arena1, root1) = Arena::with_data(1usize);
+        let child1 = root1.append(&mut arena1, 2usize);
+        root1.append(&mut arena1, 3usize);
+
+        // same data but different shape
+        let (mut arena2, root2) = Arena::with_data(1usize);
+        let child2 = root2.append(&mut arena2, 2usize);
+        child2.append(&mut arena2, 3usize);
+        assert!(arena1 != arena2);
+
+        // same shape but different data
+        let mut arena3 = arena1.clone();
+        arena3[child1].data = 20;
+        assert!(arena1 != arena3);
+
+        // same trees but different children order
+        let (mut arena4, root4) = Arena::with_data(1usize);
+        root4.append(&mut arena4, 3usize);
+        root4.append(&mut arena4, 2usize);
+        assert!(arena1 != arena4);
+
+        assert!(arena1 == arena1.clone());
+        assert!(Arena::<usize>::default() == Arena::default());
+    }
+
+    #[test]
+    fn append_many_children() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let first = root.append(&mut arena, 1usize);
+
+        let tokens = arena.append_many_children(root, 2..1000usize);
+        // a single exact reservation
+        assert_eq!(arena.capacity(), arena.node_count());
+
+        let children: Vec<_> = root.children_tokens(&arena).collect();
+        assert_eq!(children[0], first);
+        assert_eq!(&children[1..], &tokens[..]);
+        let data: Vec<_> = root.children(&arena).map(|x| x.data).collect();
+        assert_eq!(data, (1..1000).collect::<Vec<_>>());
+        for &token in &tokens {
+            assert_eq!(arena[token].parent(), Some(root));
+        }
+
+        // no reservation if the free cells suffice
+        let child = tokens[0];
+        arena.uproot(tokens[1]);
+        arena.uproot(tokens[2]);
+        let capacity = arena.capacity();
+        let tokens = arena.append_many_children(child, vec![7, 8].into_iter());
+        assert_eq!(arena.capacity(), capacity);
+        let data: Vec<_> = child.children(&arena).map(|x| x.data).collect();
+        assert_eq!(&data[..], &[7, 8]);
+        assert_eq!(tokens.len(), 2);
+    }
+
+    #[test]
+    fn swap_data() {
+        let (mut arena, root) = Arena::with_data(1usize);
+        let first_child = root.append(&mut arena, 2usize);
+        let second_child = root.append(&mut arena, 3usize);
+        let grandchild = first_child.append(&mut arena, 4usize);
+
+        arena.swap_data(root, grandchild).unwrap();
+        arena.swap_data(second_child, first_child).unwrap();
+
+        let data: Vec<_> = root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[4, 3, 1, 2]);
+        let tokens: Vec<_> = root.subtree_tokens(&arena, TraversalOrder::Pre)
+            .collect();
+        assert_eq!(&tokens[..], &[root, first_child, grandchild, second_child]);
+
+        assert_eq!(arena.swap_data(root, root), Err(Error::SameToken));
+        arena.uproot(second_child);
+        assert_eq!(arena.swap_data(root, second_child), Err(Error::InvalidToken));
+        assert_eq!(arena.swap_data(second_child, root), Err(Error::InvalidToken));
+        assert_eq!(arena.swap_data(second_child, second_child),
+                   Err(Error::InvalidToken));
+        assert_eq!(arena[root].data, 4);
+    }
+
+    #[test]
+    fn deep_chain() {
+        let depth = 1_000_000;
+        let (mut arena, root) = Arena::with_data(String::from("0"));
+        let mut token = root;
+        for i in 1..depth {
+            token = token.append(&mut arena, i.to_string());
+        }
+        assert_eq!(arena.node_count(), depth);
+        assert_eq!(token.ancestors_tokens(&arena).count(), depth - 1);
+
+        // split and remove deep subtrees
+        let middle = token.ancestors_tokens(&arena).nth(depth / 2).unwrap();
+        let (other, other_root) = arena.split_at(middle);
+        assert_eq!(other.node_count() + arena.node_count(), depth);
+        assert_eq!(other_root.subtree_tokens(&other, TraversalOrder::Post).count(),
+                   other.node_count());
+        let child = root.children_tokens(&arena).next().unwrap();
+        arena.uproot(child);
+        assert_eq!(arena.node_count(), 1);
+
+        // a long free list is walked when reserving more memory
+        let data = (0..depth).map(|i| i.to_string());
+        let tokens = arena.append_many_children(root, data);
+        assert_eq!(tokens.len(), depth);
+        drop(other);
+        drop(arena);
+    }
+
+    #[test]
+    fn foreign_tokens() {
+        let (mut arena1, root1) = Arena::with_data(1usize);
+        let child1 = root1.append(&mut arena1, 2usize);
+        let (mut arena2, root2) = Arena::with_data(10usize);
+        let child2 = root2.append(&mut arena2, 20usize);
+
+        assert!(arena1.owns(child1));
+        assert!(!arena1.owns(child2));
+        assert!(arena1.get(child2).is_none());
+        assert!(arena1.get_mut(root2).is_none());
+        assert_eq!(arena1.swap_data(root1, child2), Err(Error::InvalidToken));
+
+        // clones share tokens with the original
+        let arena3 = arena1.clone();
+        assert!(arena3.owns(child1));
+        assert_eq!(arena3[child1].data, 2);
+
+        // arenas split off from another one issue their own tokens
+        let (arena4, root4) = arena1.split_at(child1);
+        assert!(arena4.owns(root4));
+        assert!(!arena4.owns(child1));
+        assert!(!arena1.owns(root4));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid token")]
+    fn foreign_token_index() {
+        let (arena1, _) = Arena::with_data(1usize);
+        let (_, root2) = Arena::with_data(10usize);
+        let _ = arena1[root2].data;
+    }
+
+    #[test]
+    fn remove() {
+        let (mut arena, root) = Arena::with_data(1usize);
+        let first_child = root.append(&mut arena, 2usize);
+        let second_child = root.append(&mut arena, 3usize);
+        let third_child = root.append(&mut arena, 4usize);
+        let grandchild1 = second_child.append(&mut arena, 10usize);
+        let grandchild2 = second_child.append(&mut arena, 20usize);
+        grandchild1.append(&mut arena, 100usize);
+
+        let children = arena.remove(second_child);
+        assert_eq!(&children[..], &[grandchild1, grandchild2]);
+        assert!(arena.get(second_child).is_none());
+
+        let data: Vec<_> = root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[1, 2, 10, 100, 20, 4]);
+        let siblings: Vec<_> = grandchild1.following_siblings_tokens(&arena)
+            .collect();
+        assert_eq!(&siblings[..], &[grandchild2, third_child]);
+        let siblings: Vec<_> = grandchild1.preceding_siblings_tokens(&arena)
+            .collect();
+        assert_eq!(&siblings[..], &[first_child]);
+        assert_eq!(arena[grandchild1].parent(), Some(root));
+        assert_eq!(arena[grandchild2].parent(), Some(root));
+    }
+
+    #[test]
+    fn stats() {
+        let mut arena = Arena::default();
+        let stats = arena.stats();
+        assert_eq!(stats, ArenaStats {
+            node_count: 0,
+            capacity: 1,
+            root_count: 0,
+            max_height: 0,
+            leaf_count: 0
+        });
+
+        let root1 = arena.new_node(1usize);
+        let child = root1.append(&mut arena, 2usize);
+        root1.append(&mut arena, 3usize);
+        child.append(&mut arena, 4usize);
+        let root2 = arena.new_node(5usize);
+        let mut token = root2;
+        for i in 0..4 {
+            token = token.append(&mut arena, i);
+        }
+        arena.new_node(10usize);
+
+        let stats = arena.stats();
+        assert_eq!(stats.node_count, 10);
+        assert_eq!(stats.capacity, arena.capacity());
+        assert_eq!(stats.root_count, 3);
+        assert_eq!(stats.max_height, 4);
+        assert_eq!(stats.leaf_count, 4);
+
+        arena.uproot(root2);
+        let stats = arena.stats();
+        assert_eq!(stats.node_count, 5);
+        assert_eq!(stats.root_count, 2);
+        assert_eq!(stats.max_height, 2);
+        assert_eq!(stats.leaf_count, 3);
+    }
+
+    #[test]
+    fn get_by_index() {
+        let (mut arena, root) = Arena::with_data(1usize);
+        let first_child = root.append(&mut arena, 2usize);
+        let second_child = root.append(&mut arena, 3usize);
+
+        for &token in &[root, first_child, second_child] {
+            let index = token.index.get();
+            assert_eq!(arena.get_by_index(index).unwrap().token(), token);
+        }
+
+        // freed slots
+        let index = first_child.index.get();
+        arena.uproot(first_child);
+        assert!(arena.get_by_index(index).is_none());
+
+        // out of range
+        assert!(arena.get_by_index(0).is_none());
+        assert!(arena.get_by_index(arena.capacity() + 1).is_none());
+    }
+
+    #[test]
+    fn move_subtree_into() {
+        let (mut arena1, root1) = Arena::with_data(1usize);
+        let first_child = root1.append(&mut arena1, 2usize);
+        let second_child = root1.append(&mut arena1, 3usize);
+        let third_child = root1.append(&mut arena1, 4usize);
+        let grandchild = second_child.append(&mut arena1, 10usize);
+        second_child.append(&mut arena1, 20usize);
+        grandchild.append(&mut arena1, 100usize);
+        grandchild.append(&mut arena1, 200usize);
+
+        let (mut arena2, root2) = Arena::with_data(0usize);
+        root2.append(&mut arena2, 0usize);
+        let moved = arena1.move_subtree_into(second_child, &mut arena2);
+
+        assert_eq!(arena1.node_count(), 3);
+        assert!(arena1.get(second_child).is_none());
+        assert!(arena1.get(grandchild).is_none());
+        let children: Vec<_> = root1.children_tokens(&arena1).collect();
+        assert_eq!(&children[..], &[first_child, third_child]);
+
+        assert_eq!(arena2.node_count(), 7);
+        assert!(arena2.owns(moved));
+        assert!(arena2[moved].parent().is_none());
+        let data: Vec<_> = moved.subtree(&arena2, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[3, 10, 100, 200, 20]);
+        let data: Vec<_> = moved.subtree(&arena2, TraversalOrder::Level)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[3, 10, 20, 100, 200]);
+    }
+
+    #[test]
+    fn filter_map_data() {
+        let (mut arena, root1) = Arena::with_data(String::from("Indo-European"));
+        let germanic = root1.append(&mut arena, String::from("Germanic"));
+        let english = germanic.append(&mut arena, String::from("English"));
+        germanic.append(&mut arena, String::from("xDutch"));
+        let romance = root1.append(&mut arena, String::from("xRomance"));
+        romance.append(&mut arena, String::from("French"));
+        let root2 = arena.new_node(String::from("Uralic"));
+        root2.append(&mut arena, String::from("Finnish"));
+        let root3 = arena.new_node(String::from("xBasque"));
+
+        let mut visited = Vec::new();
+        arena.filter_map_data(|x| {
+            visited.push(x.clone());
+            match x.starts_with('x') {
+                true => None,
+                false => Some(x.to_uppercase())
+            }
+        });
+
+        // descendants of removed nodes are never visited
+        assert!(!visited.contains(&String::from("French")));
+        assert_eq!(visited.len(), 8);
+
+        assert_eq!(arena.node_count(), 5);
+        assert!(arena.get(root3).is_none());
+        let data: Vec<_> = root1.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data.as_str())
+            .collect();
+        assert_eq!(&data[..], &["INDO-EUROPEAN", "GERMANIC", "ENGLISH"]);
+        assert_eq!(arena[english].parent(), Some(germanic));
+        let data: Vec<_> = root2.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data.as_str())
+            .collect();
+        assert_eq!(&data[..], &["URALIC", "FINNISH"]);
+    }
+
+    fn assert_last_child_invariant<T>(arena: &Arena<T>) {
+        for node in arena.allocator.iter() {
+            // walk the forward links rather than trust the field under test
+            let last = node.token.children_tokens(arena)
+                .fold(None, |_, token| Some(token));
+            assert_eq!(node.last_child, last);
+        }
+    }
+
+    #[test]
+    fn last_child_invariant() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        assert_last_child_invariant(&arena);
+        let child4 = root.append(&mut arena, 4);
+        let child5 = child4.insert_after(&mut arena, 5);
+        assert_last_child_invariant(&arena);
+        let child2 = child4.insert_before(&mut arena, 2);
+        let child3 = child2.insert_after(&mut arena, 3);
+        assert_last_child_invariant(&arena);
+        let grandchild = child3.append(&mut arena, 6);
+        child3.append(&mut arena, 7);
+        assert_last_child_invariant(&arena);
+
+        // detaching the last child
+        child5.detach(&mut arena);
+        assert_eq!(arena[root].last_child(), Some(child4));
+        assert_last_child_invariant(&arena);
+
+        // re-attaching nodes
+        child4.append_node(&mut arena, child5).unwrap();
+        assert_last_child_invariant(&arena);
+        child5.detach(&mut arena);
+        child4.insert_node_after(&mut arena, child5).unwrap();
+        assert_eq!(arena[root].last_child(), Some(child5));
+        assert_last_child_invariant(&arena);
+        child5.detach(&mut arena);
+        child1.insert_node_before(&mut arena, child5).unwrap();
+        assert_last_child_invariant(&arena);
+
+        // replacing the last child
+        let free = arena.new_node(8);
+        child4.replace_node(&mut arena, free).unwrap();
+        assert_eq!(arena[root].last_child(), Some(free));
+        assert_last_child_invariant(&arena);
+
+        // removing nodes
+        arena.uproot(free);
+        assert_last_child_invariant(&arena);
+        let promoted = arena.remove(child3);
+        assert_eq!(promoted[0], grandchild);
+        assert_eq!(arena[root].last_child(), Some(promoted[1]));
+        assert_last_child_invariant(&arena);
+        child2.append(&mut arena, 9);
+        child2.detach_children(&mut arena);
+        assert!(arena[child2].last_child().is_none());
+        assert_last_child_invariant(&arena);
+
+        // splitting and copying
+        let (other, other_root) = arena.split_at(child2);
+        assert_last_child_invariant(&arena);
+        assert_last_child_invariant(&other);
+        arena.copy_and_append_subtree(root, &other, other_root);
+        assert_last_child_invariant(&arena);
+        let tokens = arena.append_many_children(child1, 10..13);
+        assert_eq!(arena[child1].last_child(), tokens.last().cloned());
+        assert_last_child_invariant(&arena);
+        arena.remove(root);
+        assert_last_child_invariant(&arena);
+    }
+
+    #[test]
+    fn wide_append() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let mut last = root;
+        for i in 1..100_001 {
+            last = root.append(&mut arena, i);
+        }
+        assert_eq!(arena[root].last_child(), Some(last));
+        assert_eq!(root.children_tokens(&arena).count(), 100_000);
+    }
+
+    #[test]
+    fn reclaimable() {
+        let mut arena = Arena::new();
+        let root = arena.new_node(0usize);
+        let tokens = arena.append_many_children(root, 1..10);
+        let baseline = arena.reclaimable();
+        assert_eq!(baseline, arena.capacity() - arena.node_count());
+
+        // freeing interior nodes does not make anything reclaimable
+        arena.uproot(tokens[2]);
+        arena.uproot(tokens[4]);
+        assert_eq!(arena.reclaimable(), baseline);
+
+        // freeing tail nodes does
+        arena.uproot(tokens[8]);
+        assert_eq!(arena.reclaimable(), baseline + 1);
+        arena.uproot(tokens[7]);
+        arena.uproot(tokens[6]);
+        assert_eq!(arena.reclaimable(), baseline + 3);
+
+        // the freed tail nodes are reused first
+        arena.new_node(10);
+        assert!(arena.reclaimable() < baseline + 3);
+    }
+
+    #[test]
+    fn replace_subtree_from() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 3);
+        child2.append(&mut arena, 20);
+        child2.append(&mut arena, 21).append(&mut arena, 210);
+
+        let (mut source, source_root) = Arena::with_data(100usize);
+        let source_child = source_root.append(&mut source, 101);
+        source_child.append(&mut source, 102);
+        source_root.append(&mut source, 103);
+
+        arena.replace_subtree_from(child2, &source, source_root);
+        assert_eq!(arena.node_count(), 8);
+        assert_eq!(arena[child2].data, 2);
+        assert_eq!(arena[child2].parent(), Some(root));
+        assert_eq!(arena[child2].previous_sibling(), Some(child1));
+        assert_eq!(arena[child2].next_sibling(), Some(child3));
+
+        let children: Vec<_> = child2.children(&arena).map(|x| x.data)
+            .collect();
+        assert_eq!(&children[..], &[100]);
+        let copy = arena[child2].first_child().unwrap();
+        let copied: Vec<_> = copy.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        let original: Vec<_> = source_root.subtree(&source, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(copied, original);
+    }
+
+    #[test]
+    fn step_traversals() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        child1.append(&mut arena, 3).append(&mut arena, 4);
+        child1.append(&mut arena, 5);
+        child2.append(&mut arena, 6);
+        child2.append(&mut arena, 7).append(&mut arena, 8);
+
+        for &subtree_root in &[root, child1, child2] {
+            let expected: Vec<_> = subtree_root
+                .subtree_tokens(&arena, TraversalOrder::Pre)
+                .collect();
+            let mut walk = vec![subtree_root];
+            while let Some(next) = arena.step_preorder(*walk.last().unwrap(),
+                                                       subtree_root) {
+                walk.push(next);
+            }
+            assert_eq!(walk, expected);
+
+            let expected: Vec<_> = subtree_root
+                .subtree_tokens(&arena, TraversalOrder::Post)
+                .collect();
+            let mut walk = vec![expected[0]];
+            while let Some(next) = arena.step_postorder(*walk.last().unwrap(),
+                                                        subtree_root) {
+                walk.push(next);
+            }
+            assert_eq!(walk, expected);
+        }
+    }
+
+    #[test]
+    fn into_iter() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let tokens = arena.append_many_children(root, 1..10);
+        tokens[3].append(&mut arena, 10);
+        arena.uproot(tokens[1]);
+        arena.uproot(tokens[5]);
+        arena.uproot(tokens[8]);
+
+        let mut count = 0;
+        for node in &arena {
+            assert!(std::ptr::eq(&arena[node.token()], node));
+            count += 1;
+        }
+        assert_eq!(count, arena.node_count());
+        assert_eq!((&arena).into_iter().count(), 8);
+    }
+
+    #[test]
+    fn growth_policy() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        assert_eq!(arena.growth_policy(), GrowthPolicy::Double);
+        arena.set_growth_policy(GrowthPolicy::Fixed(8));
+        assert_eq!(arena.capacity(), 1);
+
+        let mut capacities = vec![arena.capacity()];
+        for i in 1..40 {
+            root.append(&mut arena, i);
+            if arena.capacity() != *capacities.last().unwrap() {
+                capacities.push(arena.capacity());
+            }
+        }
+        assert_eq!(&capacities[..], &[1, 9, 17, 25, 33, 41]);
+
+        // back to doubling
+        arena.set_growth_policy(GrowthPolicy::Double);
+        root.append(&mut arena, 40);
+        assert_eq!(arena.capacity(), 41);
+        root.append(&mut arena, 41);
+        assert_eq!(arena.capacity(), 82);
+
+        // a fixed policy always grows the arena by at least one slot
+        let (mut arena, root) = Arena::with_data(0usize);
+        arena.set_growth_policy(GrowthPolicy::Fixed(0));
+        root.append(&mut arena, 1);
+        assert_eq!(arena.capacity(), 2);
+    }
+
+    #[test]
+    fn check_integrity() {
+        let build = || {
+            let (mut arena, root) = Arena::with_data(0usize);
+            let child1 = root.append(&mut arena, 1);
+            let child2 = root.append(&mut arena, 2);
+            let child3 = root.append(&mut arena, 3);
+            let grandchild = child2.append(&mut arena, 20);
+            arena.new_node(4);
+            (arena, [root, child1, child2, child3, grandchild])
+        };
+        let (arena, _) = build();
+        assert_eq!(arena.check_integrity(), Ok(()));
+
+        // a sibling link that points nowhere
+        let (mut arena, [_, child1, _, child3, _]) = build();
+        arena.uproot(child3);
+        arena[child1].next_sibling = Some(child3);
+        assert_eq!(arena.check_integrity(),
+                   Err(IntegrityError::DanglingLink(child1)));
+
+        // sibling links that disagree
+        let (mut arena, [_, child1, _, child3, _]) = build();
+        arena[child1].next_sibling = Some(child3);
+        assert!(matches!(arena.check_integrity(),
+                         Err(IntegrityError::SiblingMismatch(_))));
+
+        // a child that does not point back to its parent
+        let (mut arena, [root, _, _, _, grandchild]) = build();
+        arena[grandchild].parent = Some(root);
+        assert!(matches!(arena.check_integrity(),
+                         Err(IntegrityError::ParentMismatch(_))));
+
+        // a stale last child
+        let (mut arena, [root, _, child2, _, _]) = build();
+        arena[root].last_child = Some(child2);
+        assert_eq!(arena.check_integrity(),
+                   Err(IntegrityError::ChildMismatch(root)));
+
+        // a cycle that cannot be reached from any root
+        let (mut arena, [root, child1, child2, child3, grandchild]) = build();
+        arena[grandchild].first_child = Some(child2);
+        arena[grandchild].last_child = Some(child2);
+        arena[child2].parent = Some(grandchild);
+        arena[child2].previous_sibling = None;
+        arena[child2].next_sibling = None;
+        arena[child1].next_sibling = Some(child3);
+        arena[child3].previous_sibling = Some(child1);
+        assert_eq!(arena[root].first_child(), Some(child1));
+        assert!(matches!(arena.check_integrity(),
+                         Err(IntegrityError::Cycle(_))));
+    }
+
+    #[test]
+    fn stable_id() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let grandchild1 = child1.append(&mut arena, 10);
+        let grandchild2 = child2.append(&mut arena, 20);
+        let ids: Vec<_> = [root, child1, child2, grandchild1, grandchild2].iter()
+            .map(|&token| arena.stable_id(token))
+            .collect();
+
+        // ids survive structural changes
+        grandchild1.detach(&mut arena);
+        child2.append_node(&mut arena, grandchild1).unwrap();
+        grandchild2.rotate_up(&mut arena).unwrap();
+        arena.remove(child2);
+        for (&id, &token) in ids.iter().zip(&[root, child1, child2, grandchild1,
+                                              grandchild2]) {
+            match token == child2 {
+                true => assert!(arena.resolve_stable(id).is_none()),
+                false => assert_eq!(arena.resolve_stable(id), Some(token))
+            }
+        }
+
+        // ids of removed nodes never resolve again, even when their slots are
+        // reused
+        arena.uproot(child1);
+        assert_eq!(arena.stable_ids.len(), 3);
+        let new1 = root.append(&mut arena, 3);
+        let new2 = root.append(&mut arena, 4);
+        assert!([child1, child2].contains(&new1));
+        assert!([child1, child2].contains(&new2));
+        assert!(arena.resolve_stable(ids[1]).is_none());
+        assert!(arena.resolve_stable(ids[2]).is_none());
+        let new_id = arena.stable_id(new1);
+        assert!(!ids.contains(&new_id));
+        assert_eq!(arena.resolve_stable(new_id), Some(new1));
+
+        // removing subtrees forgets the ids of all descendants
+        arena.uproot(root);
+        assert!(arena.is_empty());
+        assert!(arena.stable_ids.is_empty());
+    }
+
+    #[test]
+    fn detach_returning_roots() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let grandchild = child1.append(&mut arena, 10);
+        let great_grandchild = grandchild.append(&mut arena, 100);
+
+        let (original, detached) = arena.detach_returning_roots(grandchild);
+        assert_eq!((original, detached), (root, grandchild));
+        for &token in &[original, detached] {
+            assert!(arena[token].parent().is_none());
+        }
+        assert_eq!(arena[great_grandchild].parent(), Some(grandchild));
+        let children: Vec<_> = root.children_tokens(&arena).collect();
+        assert_eq!(&children[..], &[child1, child2]);
+        assert!(child1.is_leaf(&arena));
+
+        // detaching a root is a no-op
+        let (original, detached) = arena.detach_returning_roots(grandchild);
+        assert_eq!((original, detached), (grandchild, grandchild));
+        assert_eq!(arena.check_integrity(), Ok(()));
+    }
+
+    #[test]
+    fn reparent_many() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let grandchild1 = child1.append(&mut arena, 10);
+        let grandchild2 = child2.append(&mut arena, 20);
+        let great_grandchild = grandchild2.append(&mut arena, 200);
+        let target = child2.append(&mut arena, 21);
+        let free = arena.new_node(3);
+
+        arena.reparent_many(target, &[great_grandchild, free, grandchild1])
+            .unwrap();
+        let children: Vec<_> = target.children_tokens(&arena).collect();
+        assert_eq!(&children[..], &[great_grandchild, free, grandchild1]);
+        assert!(child1.is_leaf(&arena));
+        assert!(grandchild2.is_leaf(&arena));
+        assert_eq!(arena.check_integrity(), Ok(()));
+
+        // rejected operations leave the arena untouched
+        let snapshot = arena.clone();
+        assert_eq!(arena.reparent_many(target, &[child1, child2]),
+                   Err(Error::WouldCycle));
+        assert_eq!(arena.reparent_many(target, &[target]),
+                   Err(Error::WouldCycle));
+        assert_eq!(arena.reparent_many(target, &[child1, child1]),
+                   Err(Error::SameToken));
+        arena.uproot(child1);
+        assert_eq!(arena.reparent_many(target, &[grandchild2, child1]),
+                   Err(Error::InvalidToken));
+        assert!(arena[grandchild2].parent() == Some(child2));
+        assert_eq!(snapshot[target].first_child(), arena[target].first_child());
+        assert_eq!(arena.check_integrity(), Ok(()));
+    }
+
+    #[test]
+    fn root_data() {
+        let mut arena = Arena::new();
+        assert!(arena.root_data().is_none());
+
+        let root = arena.new_node(0usize);
+        assert_eq!(arena.root_data(), Some(&0));
+        let child = root.append(&mut arena, 1);
+        child.append(&mut arena, 10);
+        assert_eq!(arena.root_data(), Some(&0));
+
+        let other_root = arena.new_node(2);
+        assert!(arena.root_data().is_none());
+        child.detach(&mut arena);
+        assert!(arena.root_data().is_none());
+
+        arena.uproot(root);
+        arena.uproot(child);
+        assert_eq!(arena.root_data(), Some(&2));
+        arena.uproot(other_root);
+        assert!(arena.root_data().is_none());
+    }
+
+    #[test]
+    fn remove_first_matching() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let grandchild1 = child1.append(&mut arena, 10);
+        let grandchild2 = child1.append(&mut arena, 11);
+        let great_grandchild = grandchild1.append(&mut arena, 100);
+        let other_root = arena.new_node(1);
+
+        // the first node in slot order is picked when several match
+        assert_eq!(arena.remove_first_matching(|&x| x == 1), Some(child1));
+        for token in &[child1, grandchild1, grandchild2, great_grandchild] {
+            assert!(arena.get(*token).is_none());
+        }
+        assert_eq!(arena.node_count(), 3);
+        let children: Vec<_> = root.children_tokens(&arena).collect();
+        assert_eq!(&children[..], &[child2]);
+
+        assert_eq!(arena.remove_first_matching(|&x| x == 1), Some(other_root));
+        assert!(arena.remove_first_matching(|&x| x == 1).is_none());
+        assert_eq!(arena.node_count(), 2);
+        assert_eq!(arena.check_integrity(), Ok(()));
+    }
+
+    #[test]
+    fn from_preorder_depths() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        root.append(&mut arena, 3);
+        let grandchild1 = child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11);
+        child2.append(&mut arena, 20);
+        grandchild1.append(&mut arena, 100);
+
+        let items: Vec<_> = root.subtree_tokens(&arena, TraversalOrder::Pre)
+            .map(|token| (token.ancestors_tokens(&arena).count(), arena[token].data))
+            .collect();
+        let (rebuilt, rebuilt_root) = Arena::from_preorder_depths(items.clone())
+            .unwrap();
+        assert_eq!(rebuilt.node_count(), arena.node_count());
+        let rebuilt_items: Vec<_> = rebuilt_root
+            .subtree_tokens(&rebuilt, TraversalOrder::Pre)
+            .map(|token| (token.ancestors_tokens(&rebuilt).count(),
+                          rebuilt[token].data))
+            .collect();
+        assert_eq!(items, rebuilt_items);
+        assert_eq!(rebuilt.check_integrity(), Ok(()));
+
+        let (single, token) = Arena::from_preorder_depths(vec![(0, 1usize)])
+            .unwrap();
+        assert_eq!(single.node_count(), 1);
+        assert_eq!(single[token].data, 1);
+
+        let invalid: Vec<Vec<(usize, usize)>> = vec![
+            vec![],
+            vec![(1, 0)],
+            vec![(0, 0), (1, 1), (0, 2)],
+            vec![(0, 0), (1, 1), (3, 2)],
+        ];
+        for items in invalid {
+            assert!(matches!(Arena::from_preorder_depths(items),
+                             Err(Error::InvalidDepth)));
+        }
+    }
+
+    #[test]
+    fn remove_default_leaves() {
+        let (mut arena, root) = Arena::with_data(1usize);
+        let child1 = root.append(&mut arena, 0);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 0);
+        let grandchild1 = child1.append(&mut arena, 0);
+        let grandchild2 = child1.append(&mut arena, 0);
+        let grandchild3 = child2.append(&mut arena, 0);
+        let grandchild4 = child3.append(&mut arena, 3);
+        grandchild1.append(&mut arena, 0);
+        let lone_default = arena.new_node(0);
+        let lone = arena.new_node(4);
+
+        arena.remove_default_leaves();
+        let data: Vec<_> = root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[1, 2, 0, 3]);
+        for token in &[child1, grandchild1, grandchild2, grandchild3, lone_default] {
+            assert!(arena.get(*token).is_none());
+        }
+        // default-valued nodes that still have children survive
+        assert_eq!(arena[grandchild4].parent(), Some(child3));
+        assert_eq!(arena[lone].data, 4);
+        assert_eq!(arena.node_count(), 5);
+        assert_eq!(arena.check_integrity(), Ok(()));
+
+        // a tree made of default values goes away entirely
+        let (mut arena, root) = Arena::with_data(0usize);
+        root.append(&mut arena, 0).append(&mut arena, 0);
+        arena.remove_default_leaves();
+        assert!(arena.is_empty());
+    }
+
+    #[test]
+    fn get_path() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        child1.append(&mut arena, 10);
+        let grandchild = child2.append(&mut arena, 20);
+        grandchild.append(&mut arena, 200);
+        grandchild.append(&mut arena, 201);
+
+        assert_eq!(arena.get_path(root, &[]).map(|x| x.data), Some(0));
+        assert_eq!(arena.get_path(root, &[1, 0, 1]).map(|x| x.data), Some(201));
+        assert_eq!(arena.get_path(child2, &[0, 0]).map(|x| x.data), Some(200));
+
+        assert!(arena.get_path(root, &[2]).is_none());
+        assert!(arena.get_path(root, &[0, 0, 0]).is_none());
+        assert!(arena.get_path(root, &[1, 0, 2]).is_none());
+
+        let (_, foreign) = Arena::with_data(0usize);
+        assert!(arena.get_path(foreign, &[]).is_none());
+    }
+
+    #[test]
+    fn sort_free_list() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let tokens: Vec<_> = (1..20).map(|i| root.append(&mut arena, i))
+            .collect();
+        for &i in &[7, 2, 15, 11, 3, 18, 0] {
+            arena.uproot(tokens[i]);
+        }
+        let capacity = arena.capacity();
+        arena.sort_free_list();
+
+        let new_tokens: Vec<_> = (0..capacity - arena.node_count() + 2)
+            .map(|i| arena.new_node(100 + i))
+            .collect();
+        for pair in new_tokens.windows(2) {
+            assert!(pair[0].index < pair[1].index);
+        }
+        let mut removed: Vec<_> = [7, 2, 15, 11, 3, 18, 0].iter()
+            .map(|&i| tokens[i])
+            .collect();
+        removed.sort_by_key(|token| token.index);
+        assert_eq!(&new_tokens[..7], &removed[..]);
+
+        // live nodes are untouched
+        let data: Vec<_> = root.children(&arena).map(|x| x.data).collect();
+        assert_eq!(&data[..], &[2, 5, 6, 7, 9, 10, 11, 13, 14, 15, 17, 18]);
+        assert_eq!(arena.check_integrity(), Ok(()));
+    }
+
+    #[test]
+    fn diff() {
+        let (mut arena_a, root_a) = Arena::with_data(0usize);
+        let child1 = root_a.append(&mut arena_a, 1);
+        let child2 = root_a.append(&mut arena_a, 2);
+        child1.append(&mut arena_a, 10);
+        child1.append(&mut arena_a, 11);
+        child2.append(&mut arena_a, 20);
+
+        let (mut arena_b, root_b) = Arena::with_data(0usize);
+        let child1 = root_b.append(&mut arena_b, 1);
+        let child2 = root_b.append(&mut arena_b, 2);
+        child1.append(&mut arena_b, 10);
+        child1.append(&mut arena_b, 12);
+        child2.append(&mut arena_b, 20);
+        child2.append(&mut arena_b, 21);
+
+        assert!(arena_a.diff(&arena_a, root_a, root_a).is_empty());
+        assert_eq!(arena_a.diff(&arena_b, root_a, root_b), vec![
+            TreeDiff::Changed { path: vec![0, 1], old: 11, new: 12 },
+            TreeDiff::Added { path: vec![1, 1], data: 21 }
+        ]);
+        assert_eq!(arena_b.diff(&arena_a, root_b, root_a), vec![
+            TreeDiff::Changed { path: vec![0, 1], old: 12, new: 11 },
+            TreeDiff::Removed { path: vec![1, 1], data: 21 }
+        ]);
+
+        // whole subtrees are reported node by node
+        let grandchild = child2.append(&mut arena_b, 22);
+        grandchild.append(&mut arena_b, 220);
+        let diffs = arena_a.diff(&arena_b, root_a, root_b);
+        assert_eq!(&diffs[2..], &[
+            TreeDiff::Added { path: vec![1, 2], data: 22 },
+            TreeDiff::Added { path: vec![1, 2, 0], data: 220 }
+        ]);
+        assert_eq!(arena_a.diff(&arena_b, root_a, child1), vec![
+            TreeDiff::Changed { path: vec![], old: 0, new: 1 },
+            TreeDiff::Changed { path: vec![0], old: 1, new: 10 },
+            TreeDiff::Removed { path: vec![0, 0], data: 10 },
+            TreeDiff::Removed { path: vec![0, 1], data: 11 },
+            TreeDiff::Changed { path: vec![1], old: 2, new: 12 },
+            TreeDiff::Removed { path: vec![1, 0], data: 20 }
+        ]);
+    }
+
+    #[test]
+    fn balanced_from_slice() {
+        let (arena, root) = Arena::balanced_from_slice(0..13usize, 3);
+        assert_eq!(arena.node_count(), 13);
+        assert_eq!(arena[root].data, 0);
+        for node in root.subtree(&arena, TraversalOrder::Pre) {
+            match node.parent() {
+                None => assert_eq!(node.token(), root),
+                Some(parent) => assert_eq!(arena[parent].data, (node.data - 1) / 3)
+            }
+            let children: Vec<_> = node.children(&arena).map(|x| x.data).collect();
+            let expected: Vec<_> = (3 * node.data + 1..3 * node.data + 4)
+                .filter(|&i| i < 13)
+                .collect();
+            assert_eq!(children, expected);
+        }
+        assert!(root.is_balanced(&arena, 0));
+
+        let (arena, root) = Arena::balanced_from_slice(vec!["root"], 2);
+        assert_eq!(arena.node_count(), 1);
+        assert!(root.is_leaf(&arena));
+    }
+
+    #[test]
+    fn canonicalize() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 1);
+        let child3 = root.append(&mut arena, 1);
+        let child4 = root.append(&mut arena, 1);
+        let leaf1 = child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11);
+        let leaf2 = child2.append(&mut arena, 10);
+        child2.append(&mut arena, 11);
+        // same nodes, different order
+        child3.append(&mut arena, 11);
+        child3.append(&mut arena, 10);
+        // same data, different shape
+        child4.append(&mut arena, 10).append(&mut arena, 11);
+        // a duplicate in another tree
+        let other_root = arena.new_node(1);
+        other_root.append(&mut arena, 10);
+        other_root.append(&mut arena, 11);
+
+        let ids = arena.canonicalize();
+        assert_eq!(ids.len(), arena.node_count());
+        assert_eq!(ids[&child1], ids[&child2]);
+        assert_eq!(ids[&child1], ids[&other_root]);
+        assert_eq!(ids[&leaf1], ids[&leaf2]);
+        assert_ne!(ids[&child1], ids[&child3]);
+        assert_ne!(ids[&child1], ids[&child4]);
+        assert_ne!(ids[&child3], ids[&child4]);
+        assert_ne!(ids[&root], ids[&child1]);
+
+        // equal ids mean equal subtrees
+        for a in root.subtree_tokens(&arena, TraversalOrder::Pre) {
+            for b in root.subtree_tokens(&arena, TraversalOrder::Pre) {
+                let same = arena.diff(&arena, a, b).is_empty();
+                assert_eq!(ids[&a] == ids[&b], same);
+            }
+        }
+    }
+
+    #[test]
+    fn dedup_report() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        assert!(arena.dedup_report().is_empty());
+
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 1);
+        let child3 = root.append(&mut arena, 2);
+        for &child in &[child1, child2] {
+            child.append(&mut arena, 10);
+            child.append(&mut arena, 11).append(&mut arena, 110);
+        }
+        let leaf1 = child3.append(&mut arena, 30);
+        let leaf2 = child3.append(&mut arena, 30);
+        let other_root = arena.new_node(1);
+        other_root.append(&mut arena, 10);
+        other_root.append(&mut arena, 11).append(&mut arena, 110);
+        let lone_leaf = arena.new_node(30);
+
+        let groups = arena.dedup_report();
+        assert_eq!(groups, vec![vec![child1, child2, other_root],
+                                vec![leaf1, leaf2, lone_leaf]]);
+
+        // breaking a duplicate splits it off its group, and its children are
+        // not reported since their copies are nested in the remaining group
+        child2.append(&mut arena, 12);
+        let groups = arena.dedup_report();
+        assert_eq!(groups, vec![vec![child1, other_root],
+                                vec![leaf1, leaf2, lone_leaf]]);
+    }
+
+    #[test]
+    fn all_valid() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let grandchild = child1.append(&mut arena, 10);
+
+        assert!(arena.all_valid(&[]));
+        assert!(arena.all_valid(&[root, child1, child2, grandchild]));
+        assert!(arena.all_valid(&[child2, child2]));
+
+        arena.uproot(child1);
+        assert!(!arena.all_valid(&[root, child2, grandchild]));
+        assert!(!arena.all_valid(&[child1]));
+        assert!(arena.all_valid(&[root, child2]));
+
+        let (_, foreign) = Arena::with_data(0usize);
+        assert!(!arena.all_valid(&[root, foreign]));
+    }
+
+    #[test]
+    fn swap_subtrees() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 3);
+        let child4 = root.append(&mut arena, 4);
+        let grandchild1 = child1.append(&mut arena, 10);
+        let grandchild2 = child1.append(&mut arena, 11);
+        let grandchild3 = child3.append(&mut arena, 30);
+        grandchild1.append(&mut arena, 100);
+
+        let preorder = |arena: &Arena<usize>| -> Vec<usize> {
+            root.subtree(arena, TraversalOrder::Pre).map(|x| x.data).collect()
+        };
+
+        // non-adjacent siblings, including the first child
+        arena.swap_subtrees(child1, child3).unwrap();
+        assert_eq!(preorder(&arena), &[0, 3, 30, 2, 1, 10, 100, 11, 4]);
+        assert_eq!(arena[root].first_child(), Some(child3));
+        assert_last_child_invariant(&arena);
+
+        // adjacent siblings in both argument orders, including the last child
+        arena.swap_subtrees(child1, child4).unwrap();
+        assert_eq!(preorder(&arena), &[0, 3, 30, 2, 4, 1, 10, 100, 11]);
+        assert_eq!(arena[root].last_child(), Some(child1));
+        arena.swap_subtrees(child4, child2).unwrap();
+        assert_eq!(preorder(&arena), &[0, 3, 30, 4, 2, 1, 10, 100, 11]);
+        assert_last_child_invariant(&arena);
+
+        // different branches at different depths
+        arena.swap_subtrees(grandchild3, grandchild1).unwrap();
+        assert_eq!(preorder(&arena), &[0, 3, 10, 100, 4, 2, 1, 30, 11]);
+        assert_eq!(arena[grandchild1].parent(), Some(child3));
+        assert_eq!(arena[grandchild3].parent(), Some(child1));
+        arena.swap_subtrees(grandchild2, child4).unwrap();
+        assert_eq!(preorder(&arena), &[0, 3, 10, 100, 11, 2, 1, 30, 4]);
+        assert_last_child_invariant(&arena);
+
+        // a free-standing tree trades places with a node
+        let other = arena.new_node(5);
+        other.append(&mut arena, 50);
+        arena.swap_subtrees(other, child2).unwrap();
+        assert_eq!(preorder(&arena), &[0, 3, 10, 100, 11, 5, 50, 1, 30, 4]);
+        assert!(arena[child2].parent().is_none());
+        assert!(arena[child2].next_sibling().is_none());
+        assert_last_child_invariant(&arena);
+
+        // errors leave the arena untouched
+        assert_eq!(arena.swap_subtrees(root, grandchild1), Err(Error::WouldCycle));
+        assert_eq!(arena.swap_subtrees(grandchild1, child3), Err(Error::WouldCycle));
+        assert_eq!(arena.swap_subtrees(child3, child3), Err(Error::SameToken));
+        arena.uproot(child2);
+        assert_eq!(arena.swap_subtrees(child2, child3), Err(Error::InvalidToken));
+        assert_eq!(arena.swap_subtrees(child3, child2), Err(Error::InvalidToken));
+        assert_eq!(preorder(&arena), &[0, 3, 10, 100, 11, 5, 50, 1, 30, 4]);
+        assert_eq!(arena.node_count(), 10);
+    }
+}
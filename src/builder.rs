@@ -0,0 +1,169 @@
+//! A module that contains a builder for constructing arenas one node at a time.
+use crate::arena::Arena;
+use crate::token::Token;
+use crate::Error;
+
+/// A builder that constructs an `Arena<T>` by adding roots and children one at
+/// a time. Parent references are validated as nodes are added.
+///
+/// # Examples:
+///
+/// ```
+/// use atree::ArenaBuilder;
+///
+/// let mut builder = ArenaBuilder::new();
+/// let root = builder.add_root("Indo-European");
+/// let germanic = builder.add_child(root, "Germanic").unwrap();
+/// builder.add_child(germanic, "English").unwrap();
+///
+/// let (arena, roots) = builder.finish();
+/// assert_eq!(arena.node_count(), 3);
+/// assert_eq!(&roots[..], &[root]);
+/// assert_eq!(arena[germanic].data, "Germanic");
+/// ```
+pub struct ArenaBuilder<T> {
+    arena: Arena<T>,
+    roots: Vec<Token>
+}
+
+impl<T> ArenaBuilder<T> {
+    /// Creates a new empty builder.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::ArenaBuilder;
+    ///
+    /// let builder: ArenaBuilder<usize> = ArenaBuilder::new();
+    /// let (arena, roots) = builder.finish();
+    /// assert!(arena.is_empty());
+    /// assert!(roots.is_empty());
+    /// ```
+    pub fn new() -> Self {
+        ArenaBuilder { arena: Arena::new(), roots: Vec::new() }
+    }
+
+    /// Adds a new root node with the given data and returns its token.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::ArenaBuilder;
+    ///
+    /// let mut builder = ArenaBuilder::new();
+    /// let root1 = builder.add_root("Indo-European");
+    /// let root2 = builder.add_root("Uralic");
+    ///
+    /// let (arena, roots) = builder.finish();
+    /// assert_eq!(&roots[..], &[root1, root2]);
+    /// assert_eq!(arena[root2].data, "Uralic");
+    /// ```
+    pub fn add_root(&mut self, data: T) -> Token {
+        let token = self.arena.new_node(data);
+        self.roots.push(token);
+        token
+    }
+
+    /// Appends a new node with the given data as the last child of `parent`
+    /// and returns its token.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `Err(Error::InvalidToken)` if `parent` does not refer to a node
+    /// previously added to this builder.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::{ArenaBuilder, Error};
+    ///
+    /// let mut builder = ArenaBuilder::new();
+    /// let root = builder.add_root("Indo-European");
+    /// let romance = builder.add_child(root, "Romance").unwrap();
+    ///
+    /// let mut other = ArenaBuilder::new();
+    /// let foreign = other.add_root("Uralic");
+    /// assert_eq!(builder.add_child(foreign, "Finnish"), Err(Error::InvalidToken));
+    ///
+    /// let (arena, _) = builder.finish();
+    /// assert_eq!(arena[romance].parent(), Some(root));
+    /// ```
+    pub fn add_child(&mut self, parent: Token, data: T) -> Result<Token, Error> {
+        match self.arena.get(parent) {
+            None => Err(Error::InvalidToken),
+            Some(_) => Ok(parent.append(&mut self.arena, data))
+        }
+    }
+
+    /// Consumes the builder and returns the constructed arena along with the
+    /// tokens of its roots in the order they were added.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::ArenaBuilder;
+    ///
+    /// let mut builder = ArenaBuilder::new();
+    /// let root = builder.add_root(1usize);
+    /// builder.add_child(root, 2).unwrap();
+    ///
+    /// let (arena, roots) = builder.finish();
+    /// assert_eq!(arena.node_count(), 2);
+    /// assert_eq!(&roots[..], &[root]);
+    /// ```
+    pub fn finish(self) -> (Arena<T>, Vec<Token>) {
+        (self.arena, self.roots)
+    }
+}
+
+impl<T> Default for ArenaBuilder<T> {
+    fn default() -> Self { ArenaBuilder::new() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::iter::TraversalOrder;
+
+    #[test]
+    fn build_multi_level() {
+        let mut builder = ArenaBuilder::new();
+        let root = builder.add_root("Indo-European");
+        let romance = builder.add_child(root, "Romance").unwrap();
+        let germanic = builder.add_child(root, "Germanic").unwrap();
+        builder.add_child(romance, "French").unwrap();
+        let west = builder.add_child(germanic, "West").unwrap();
+        builder.add_child(west, "English").unwrap();
+        builder.add_child(west, "Scots").unwrap();
+        let uralic = builder.add_root("Uralic");
+        builder.add_child(uralic, "Finnish").unwrap();
+
+        let (arena, roots) = builder.finish();
+        assert_eq!(&roots[..], &[root, uralic]);
+        assert_eq!(arena.node_count(), 9);
+
+        let data: Vec<_> = root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &["Indo-European", "Romance", "French",
+                                "Germanic", "West", "English", "Scots"]);
+        let data: Vec<_> = uralic.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &["Uralic", "Finnish"]);
+    }
+
+    #[test]
+    fn unknown_parent() {
+        let mut other = ArenaBuilder::new();
+        let foreign = other.add_root(0usize);
+        other.add_child(foreign, 1).unwrap();
+
+        let mut builder = ArenaBuilder::new();
+        assert_eq!(builder.add_child(foreign, 1), Err(Error::InvalidToken));
+        let root = builder.add_root(0usize);
+        assert!(builder.add_child(root, 1).is_ok());
+        let (arena, _) = builder.finish();
+        assert_eq!(arena.node_count(), 2);
+    }
+}
@@ -0,0 +1,222 @@
+//! A module that contains cursors and zippers for stateful navigation of the
+//! trees.
+use crate::arena::Arena;
+use crate::node::Node;
+use crate::token::Token;
+
+/// A cursor that points at a node in the arena and can be moved around the
+/// tree it belongs to.
+///
+/// This `struct` is created by the [`cursor`] method on `Arena<T>`. See its
+/// documentation for more.
+///
+/// [`cursor`]: struct.Arena.html#method.cursor
+pub struct Cursor<'a, T> {
+    pub (crate) arena: &'a Arena<T>,
+    pub (crate) token: Token
+}
+
+/// A cursor that points at a node in the arena and can be moved around the
+/// tree it belongs to. Unlike [`Cursor`], it allows mutating the data of the
+/// node under the cursor.
+///
+/// This `struct` is created by the [`cursor_mut`] method on `Arena<T>`. See
+/// its documentation for more.
+///
+/// [`Cursor`]: struct.Cursor.html
+/// [`cursor_mut`]: struct.Arena.html#method.cursor_mut
+pub struct CursorMut<'a, T> {
+    pub (crate) arena: &'a mut Arena<T>,
+    pub (crate) token: Token
+}
+
+/// A zipper that focuses on a node in the arena and can be moved around the
+/// tree it belongs to. Edits made through a zipper produce a modified copy of
+/// the arena and leave the original untouched.
+///
+/// This `struct` is created by the [`focus`] method on `Arena<T>`. See its
+/// documentation for more.
+///
+/// [`focus`]: struct.Arena.html#method.focus
+pub struct Zipper<'a, T> {
+    pub (crate) arena: &'a Arena<T>,
+    pub (crate) token: Token
+}
+
+/// A macro that implements the navigation methods shared by the cursors and zippers.
+macro_rules! navigation {
+    ($name:ident) => {
+        impl<'a, T> $name<'a, T> {
+            /// Returns the token of the node under the cursor.
+            pub fn token(&self) -> Token { self.token }
+
+            /// Returns a reference to the node under the cursor.
+            pub fn node(&self) -> &Node<T> { &self.arena[self.token] }
+
+            /// Returns a reference to the data of the node under the cursor.
+            pub fn data(&self) -> &T { &self.node().data }
+
+            /// Moves the cursor to the parent node. Returns false (and stays
+            /// put) if the current node has no parent.
+            pub fn up(&mut self) -> bool {
+                let parent = self.node().parent;
+                self.move_to(parent)
+            }
+
+            /// Moves the cursor to the first child. Returns false (and stays
+            /// put) if the current node is a leaf.
+            pub fn down(&mut self) -> bool {
+                let first_child = self.node().first_child;
+                self.move_to(first_child)
+            }
+
+            /// Moves the cursor to the previous sibling. Returns false (and
+            /// stays put) if the current node has no previous sibling.
+            pub fn left(&mut self) -> bool {
+                let previous_sibling = self.node().previous_sibling;
+                self.move_to(previous_sibling)
+            }
+
+            /// Moves the cursor to the next sibling. Returns false (and stays
+            /// put) if the current node has no next sibling.
+            pub fn right(&mut self) -> bool {
+                let next_sibling = self.node().next_sibling;
+                self.move_to(next_sibling)
+            }
+
+            fn move_to(&mut self, token: Option<Token>) -> bool {
+                match token {
+                    None => false,
+                    Some(token) => {
+                        self.token = token;
+                        true
+                    }
+                }
+            }
+        }
+    }
+}
+
+navigation!(Cursor);
+navigation!(CursorMut);
+navigation!(Zipper);
+
+impl<'a, T> CursorMut<'a, T> {
+    /// Returns a mutable reference to the node under the cursor.
+    pub fn node_mut(&mut self) -> &mut Node<T> { &mut self.arena[self.token] }
+
+    /// Returns a mutable reference to the data of the node under the cursor.
+    pub fn data_mut(&mut self) -> &mut T { &mut self.node_mut().data }
+}
+
+impl<'a, T> Zipper<'a, T> where T: Clone {
+    /// Returns a copy of the arena where the data of the node in focus is
+    /// replaced with the given data. The original arena is left unchanged.
+    /// Tokens of the original arena remain valid in the copy.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = 1usize;
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let child = root_token.append(&mut arena, 2usize);
+    ///
+    /// let edited = arena.focus(child).replace(20);
+    /// assert_eq!(edited[child].data, 20);
+    /// assert_eq!(arena[child].data, 2);
+    /// ```
+    pub fn replace(self, data: T) -> Arena<T> {
+        let mut arena = self.arena.clone();
+        arena[self.token].data = data;
+        arena
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cursor() {
+        let (mut arena, root) = Arena::with_data("Indo-European");
+        let germanic = root.append(&mut arena, "Germanic");
+        let romance = root.append(&mut arena, "Romance");
+        let english = germanic.append(&mut arena, "English");
+        let french = romance.append(&mut arena, "French");
+
+        let mut cursor = arena.cursor(root);
+        assert!(!cursor.up());
+        assert!(!cursor.left());
+        assert!(!cursor.right());
+        assert!(cursor.down());
+        assert_eq!(cursor.token(), germanic);
+        assert!(!cursor.left());
+        assert!(cursor.down());
+        assert_eq!(*cursor.data(), "English");
+        assert!(!cursor.down());
+        assert_eq!(cursor.token(), english);
+        assert!(cursor.up());
+        assert!(cursor.right());
+        assert_eq!(cursor.token(), romance);
+        assert!(!cursor.right());
+        assert!(cursor.down());
+        assert_eq!(cursor.node().token(), french);
+        assert!(cursor.up());
+        assert!(cursor.left());
+        assert!(cursor.up());
+        assert_eq!(cursor.token(), root);
+    }
+
+    #[test]
+    fn cursor_mut() {
+        let (mut arena, root) = Arena::with_data(1usize);
+        let first_child = root.append(&mut arena, 2usize);
+        let second_child = root.append(&mut arena, 3usize);
+        let grandchild = second_child.append(&mut arena, 4usize);
+
+        let mut cursor = arena.cursor_mut(root);
+        *cursor.data_mut() += 10;
+        assert!(cursor.down());
+        assert!(cursor.right());
+        *cursor.data_mut() += 10;
+        assert!(cursor.down());
+        assert_eq!(cursor.token(), grandchild);
+        cursor.data_mut().clone_from(&40);
+        assert!(!cursor.right());
+        assert!(cursor.up());
+        assert!(cursor.left());
+        assert_eq!(*cursor.data(), 2);
+
+        assert_eq!(arena[root].data, 11);
+        assert_eq!(arena[first_child].data, 2);
+        assert_eq!(arena[second_child].data, 13);
+        assert_eq!(arena[grandchild].data, 40);
+    }
+
+    #[test]
+    fn zipper() {
+        let (mut arena, root) = Arena::with_data(String::from("Indo-European"));
+        let germanic = root.append(&mut arena, String::from("Germanic"));
+        let english = germanic.append(&mut arena, String::from("English"));
+        let snapshot = arena.clone();
+
+        let mut zipper = arena.focus(root);
+        assert!(zipper.down());
+        assert!(zipper.down());
+        assert_eq!(zipper.token(), english);
+        let edited = zipper.replace(String::from("Old English"));
+
+        // the original is untouched
+        assert!(arena == snapshot);
+        assert_eq!(arena[english].data, "English");
+
+        // only the focused node changed in the copy
+        assert_eq!(edited[english].data, "Old English");
+        assert_eq!(edited[germanic].data, "Germanic");
+        assert_eq!(edited[root].data, "Indo-European");
+        assert_eq!(edited[english].parent(), Some(germanic));
+        assert_eq!(edited.node_count(), arena.node_count());
+    }
+}
@@ -1,5 +1,6 @@
 #![allow(clippy::match_bool)]
 //! A module that contains different kinds of iterators.
+use std::cmp::Ordering;
 use std::collections::VecDeque;
 use std::marker::PhantomData;
 use std::mem;
@@ -250,6 +251,88 @@ impl<'a, T> Iterator for SubtreeMut<'a, T> {
 unsafe impl<T: Sync> Sync for SubtreeMut<'_, T> {}
 unsafe impl<T: Send> Send for SubtreeMut<'_, T> {}
 
+/// An iterator of references of the subtree nodes of a given node paired with
+/// their depths relative to the node. Used in [`subtree_with_depth`].
+///
+/// [`subtree_with_depth`]: ../struct.Token.html#method.subtree_with_depth
+pub (crate) struct SubtreeWithDepth<'a, T> {
+    pub (crate) iter: SubtreeTokens<'a, T>,
+    pub (crate) order: TraversalOrder,
+    pub (crate) previous: Option<(Token, usize)>
+}
+
+impl<'a, T> SubtreeWithDepth<'a, T> {
+    fn parent(&self, token: Token) -> Option<Token> {
+        match self.iter.arena.get(token) {
+            Some(node) => node.parent,
+            None => panic!("Corrupt arena")
+        }
+    }
+
+    // Depth-first traversals only ever move from one node to its first child,
+    // to its parent, or to the next sibling of the node or one of its
+    // ancestors (in pre-order) or to the deepest first descendant of its next
+    // sibling (in post-order). The depth is adjusted by the number of levels
+    // climbed or descended on the way.
+    fn depth_first_depth(&self, token: Token, previous: Token, depth: usize)
+        -> usize {
+        let parent = self.parent(token);
+        match self.order {
+            TraversalOrder::Pre => match parent == Some(previous) {
+                true => depth + 1,
+                false => {
+                    let (mut ancestor, mut depth) = (previous, depth);
+                    while self.parent(ancestor) != parent {
+                        ancestor = self.parent(ancestor).unwrap();
+                        depth -= 1;
+                    }
+                    depth
+                }
+            },
+            _ => match self.parent(previous) == Some(token) {
+                true => depth - 1,
+                false => {
+                    let (mut descendant, mut depth) = (token, depth);
+                    while self.parent(descendant) != self.parent(previous) {
+                        descendant = self.parent(descendant).unwrap();
+                        depth += 1;
+                    }
+                    depth
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T> Iterator for SubtreeWithDepth<'a, T> {
+    type Item = (usize, &'a Node<T>);
+    fn next(&mut self) -> Option<(usize, &'a Node<T>)> {
+        // in level-order, the current level only runs out right before moving
+        // on to the next one
+        let next_level = self.iter.curr_level.is_empty();
+        let token = self.iter.next()?;
+        let root = self.iter.subtree_root;
+        let depth = match (self.order, self.previous) {
+            (TraversalOrder::Level, None) => 0,
+            (TraversalOrder::Level, Some((_, depth))) => match next_level {
+                true => depth + 1,
+                false => depth
+            },
+            (TraversalOrder::Pre, None) => 0,
+            (TraversalOrder::Post, None) => match token == root {
+                true => 0,
+                false => token.ancestors_tokens(self.iter.arena)
+                    .take_while(|&ancestor| ancestor != root)
+                    .count() + 1
+            },
+            (_, Some((previous, depth))) =>
+                self.depth_first_depth(token, previous, depth)
+        };
+        self.previous = Some((token, depth));
+        self.iter.arena.get(token).map(|node| (depth, node))
+    }
+}
+
 /// An iterator of tokens of siblings that follow a given node.
 ///
 /// This `struct` is created by the `following_siblings_tokens` methods on
@@ -259,7 +342,8 @@ unsafe impl<T: Send> Send for SubtreeMut<'_, T> {}
 /// [`Node`]: ../struct.Node.html#method.following_siblings_tokens
 pub struct FollowingSiblingTokens<'a, T> {
     pub (crate) arena: &'a Arena<T>,
-    pub (crate) node_token: Option<Token>
+    pub (crate) node_token: Option<Token>,
+    pub (crate) back_token: Option<Token>
 }
 
 /// An iterator of tokens of siblings that precede a given node.
@@ -271,7 +355,8 @@ pub struct FollowingSiblingTokens<'a, T> {
 /// [`Node`]: ../struct.Node.html#method.preceding_siblings_tokens
 pub struct PrecedingSiblingTokens<'a, T> {
     pub (crate) arena: &'a Arena<T>,
-    pub (crate) node_token: Option<Token>
+    pub (crate) node_token: Option<Token>,
+    pub (crate) back_token: Option<Token>
 }
 
 /// An iterator of tokens of the children of a given node.
@@ -282,6 +367,17 @@ pub struct PrecedingSiblingTokens<'a, T> {
 /// [`Token`]: ../struct.Token.html#method.children_tokens
 /// [`Node`]: ../struct.Node.html#method.children_tokens
 pub struct ChildrenTokens<'a, T> {
+    pub (crate) arena: &'a Arena<T>,
+    pub (crate) node_token: Option<Token>,
+    pub (crate) back_token: Option<Token>,
+    pub (crate) len: usize
+}
+
+/// An iterator of tokens of the children of a given node, from the last child
+/// to the first. Used in [`children_rev`].
+///
+/// [`children_rev`]: ../struct.Token.html#method.children_rev
+pub (crate) struct ChildrenRevTokens<'a, T> {
     pub (crate) arena: &'a Arena<T>,
     pub (crate) node_token: Option<Token>
 }
@@ -412,6 +508,71 @@ macro_rules! iterator {
         }
     };
 
+    // the two ends of the iterator step towards each other and both stop once
+    // they meet
+    (@double struct $name:ident > $field:ident < $back_field:ident $(, $len:ident)?) => {
+        impl<'a, T> Iterator for $name<'a, T> {
+            type Item = Token;
+            fn next(&mut self) -> Option<Token> {
+                match self.node_token {
+                    None => None,
+                    Some(token) => match self.arena.get(token) {
+                        None => panic!("Stale token: {:?} is not found in \
+                                        the arena. Check code", token),
+                        Some(curr_node) => {
+                            match self.back_token == Some(token) {
+                                true => {
+                                    self.node_token = None;
+                                    self.back_token = None;
+                                },
+                                false => self.node_token = curr_node.$field
+                            }
+                            $(self.$len -= 1;)?
+                            Some(token)
+                        }
+                    }
+                }
+            }
+
+            $(
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (self.$len, Some(self.$len))
+            }
+
+            fn count(self) -> usize { self.$len }
+            )?
+        }
+
+        impl<'a, T> DoubleEndedIterator for $name<'a, T> {
+            fn next_back(&mut self) -> Option<Token> {
+                match self.back_token {
+                    None => None,
+                    Some(token) => match self.arena.get(token) {
+                        None => panic!("Stale token: {:?} is not found in \
+                                        the arena. Check code", token),
+                        Some(curr_node) => {
+                            match self.node_token == Some(token) {
+                                true => {
+                                    self.node_token = None;
+                                    self.back_token = None;
+                                },
+                                false => self.back_token = curr_node.$back_field
+                            }
+                            $(self.$len -= 1;)?
+                            Some(token)
+                        }
+                    }
+                }
+            }
+        }
+
+        $(
+        impl<'a, T> ExactSizeIterator for $name<'a, T> {
+            fn len(&self) -> usize { self.$len }
+        }
+        )?
+    };
+
     // perhaps fold this into the @token branch since this can be implemented with
     // largely the same code with one less Arena::get (one less look-up should
     // translate to more performant code)
@@ -424,6 +585,23 @@ macro_rules! iterator {
                     None => None
                 }
             }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.token_iter.size_hint()
+            }
+        }
+    };
+
+    (@node_double struct $name:ident) => {
+        iterator!(@node struct $name);
+
+        impl<'a, T> DoubleEndedIterator for $name<'a, T> {
+            fn next_back(&mut self) -> Option<&'a Node<T>> {
+                match self.token_iter.next_back() {
+                    Some(node_token) => self.token_iter.arena.get(node_token),
+                    None => None
+                }
+            }
         }
     };
 
@@ -452,15 +630,114 @@ macro_rules! iterator {
     }
 }
 
-iterator!(@token struct FollowingSiblingTokens > next_sibling);
-iterator!(@token struct PrecedingSiblingTokens > previous_sibling);
-iterator!(@token struct ChildrenTokens > next_sibling);
+iterator!(@double struct FollowingSiblingTokens > next_sibling < previous_sibling);
+iterator!(@double struct PrecedingSiblingTokens > previous_sibling < next_sibling);
+iterator!(@double struct ChildrenTokens > next_sibling < previous_sibling, len);
+iterator!(@token struct ChildrenRevTokens > previous_sibling);
 iterator!(@token struct AncestorTokens > parent);
-iterator!(@node struct PrecedingSiblings);
-iterator!(@node struct FollowingSiblings);
-iterator!(@node struct Children);
+iterator!(@node_double struct PrecedingSiblings);
+iterator!(@node_double struct FollowingSiblings);
+iterator!(@node_double struct Children);
 iterator!(@node struct Ancestors);
 iterator!(@mut struct PrecedingSiblingsMut > previous_sibling);
 iterator!(@mut struct FollowingSiblingsMut > next_sibling);
 iterator!(@mut struct ChildrenMut > next_sibling);
 iterator!(@mut struct AncestorsMut > parent);
+
+impl<'a, T> ExactSizeIterator for Children<'a, T> {}
+
+/// An iterator of tokens of the subtree nodes of a given node, visited in the
+/// order of priority given by a comparator.
+///
+/// This `struct` is created by the [`best_first`] method on `Token`. See its
+/// documentation for more.
+///
+/// [`best_first`]: ../struct.Token.html#method.best_first
+pub struct BestFirstTokens<'a, T, F> {
+    pub (crate) arena: &'a Arena<T>,
+    pub (crate) heap: Vec<Token>,
+    pub (crate) cmp: F
+}
+
+impl<'a, T, F> BestFirstTokens<'a, T, F>
+    where F: FnMut(&Node<T>, &Node<T>) -> Ordering {
+    fn less(&mut self, i: usize, j: usize) -> bool {
+        let (x, y) = (&self.arena[self.heap[i]], &self.arena[self.heap[j]]);
+        (self.cmp)(x, y) == Ordering::Less
+    }
+
+    pub (crate) fn push(&mut self, token: Token) {
+        self.heap.push(token);
+        let mut i = self.heap.len() - 1;
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            match self.less(i, parent) {
+                false => break,
+                true => {
+                    self.heap.swap(i, parent);
+                    i = parent;
+                }
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<Token> {
+        if self.heap.is_empty() { return None }
+        let token = self.heap.swap_remove(0);
+        let mut i = 0;
+        loop {
+            let (left, right) = (2 * i + 1, 2 * i + 2);
+            let mut smallest = i;
+            if left < self.heap.len() && self.less(left, smallest) {
+                smallest = left;
+            }
+            if right < self.heap.len() && self.less(right, smallest) {
+                smallest = right;
+            }
+            match smallest == i {
+                true => break,
+                false => {
+                    self.heap.swap(i, smallest);
+                    i = smallest;
+                }
+            }
+        }
+        Some(token)
+    }
+}
+
+impl<'a, T, F> Iterator for BestFirstTokens<'a, T, F>
+    where F: FnMut(&Node<T>, &Node<T>) -> Ordering {
+    type Item = Token;
+    fn next(&mut self) -> Option<Token> {
+        let token = self.pop()?;
+        let arena = self.arena;
+        for child in token.children_tokens(arena) {
+            self.push(child);
+        }
+        Some(token)
+    }
+}
+
+/// An iterator of references of all the nodes in an arena, in the order they
+/// are laid out in memory.
+///
+/// This `struct` is created by iterating over a reference to an `Arena<T>`
+/// (`for node in &arena`).
+pub struct Nodes<'a, T> {
+    pub (crate) arena: &'a Arena<T>,
+    pub (crate) index: usize
+}
+
+impl<'a, T> Iterator for Nodes<'a, T> {
+    type Item = &'a Node<T>;
+    fn next(&mut self) -> Option<&'a Node<T>> {
+        while self.index < self.arena.capacity() {
+            self.index += 1;
+            if let Some(node) = self.arena.get_by_index(self.index) {
+                return Some(node)
+            }
+        }
+        None
+    }
+}
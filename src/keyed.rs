@@ -0,0 +1,263 @@
+//! A module that contains an arena whose nodes are identified by unique keys.
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::arena::Arena;
+use crate::token::{StableId, Token};
+use crate::Error;
+
+/// An arena where every node is registered under a unique key, such as a file
+/// path. Nodes can be looked up by their keys, and inserting a node under a
+/// key that is already taken is rejected.
+///
+/// Keys are tied to the [stable ids] of the nodes, so the inner arena can be
+/// restructured freely through [`arena_mut`]. The key of a node that is
+/// removed from the arena stops resolving and becomes available again.
+///
+/// # Examples:
+///
+/// ```
+/// use atree::{Error, KeyedArena};
+///
+/// let mut arena = KeyedArena::new();
+/// let root = arena.insert_root("/", "root").unwrap();
+/// let usr = arena.insert_child(root, "/usr", "usr").unwrap();
+///
+/// assert_eq!(arena.get_by_key(&"/usr"), Some(usr));
+/// assert_eq!(arena.insert_child(root, "/usr", "usr"), Err(Error::DuplicateKey));
+/// assert_eq!(arena.arena().node_count(), 2);
+/// ```
+///
+/// [stable ids]: struct.StableId.html
+/// [`arena_mut`]: struct.KeyedArena.html#method.arena_mut
+pub struct KeyedArena<K, T> {
+    arena: Arena<T>,
+    keys: HashMap<K, StableId>
+}
+
+impl<K, T> KeyedArena<K, T> where K: Eq + Hash {
+    /// Creates a new empty keyed arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::KeyedArena;
+    ///
+    /// let arena: KeyedArena<String, usize> = KeyedArena::new();
+    /// assert!(arena.arena().is_empty());
+    /// ```
+    pub fn new() -> Self {
+        KeyedArena { arena: Arena::new(), keys: HashMap::new() }
+    }
+
+    /// Creates a new free-standing node (the root of a new tree) under the
+    /// given key and returns its token.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `Err(Error::DuplicateKey)` if the key is already taken, in which
+    /// case the arena is left untouched.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::{Error, KeyedArena};
+    ///
+    /// let mut arena = KeyedArena::new();
+    /// let root = arena.insert_root("/", 0usize).unwrap();
+    /// assert_eq!(arena.get_by_key(&"/"), Some(root));
+    /// assert_eq!(arena.insert_root("/", 1usize), Err(Error::DuplicateKey));
+    /// ```
+    pub fn insert_root(&mut self, key: K, data: T) -> Result<Token, Error> {
+        if self.get_by_key(&key).is_some() { return Err(Error::DuplicateKey) }
+        let token = self.arena.new_node(data);
+        self.register(key, token);
+        Ok(token)
+    }
+
+    /// Appends a new node with the given data under the given key as the last
+    /// child of `parent` and returns its token.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `Err(Error::InvalidToken)` if `parent` does not correspond to a
+    /// node in the arena, or `Err(Error::DuplicateKey)` if the key is already
+    /// taken. The arena is left untouched in either case.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::{Error, KeyedArena};
+    ///
+    /// let mut arena = KeyedArena::new();
+    /// let root = arena.insert_root("/", "root").unwrap();
+    /// let usr = arena.insert_child(root, "/usr", "usr").unwrap();
+    /// assert_eq!(arena.arena()[usr].parent(), Some(root));
+    ///
+    /// assert_eq!(arena.insert_child(root, "/usr", "usr"),
+    ///            Err(Error::DuplicateKey));
+    /// ```
+    pub fn insert_child(&mut self, parent: Token, key: K, data: T)
+        -> Result<Token, Error> {
+        if self.arena.get(parent).is_none() { return Err(Error::InvalidToken) }
+        if self.get_by_key(&key).is_some() { return Err(Error::DuplicateKey) }
+        let token = parent.append(&mut self.arena, data);
+        self.register(key, token);
+        Ok(token)
+    }
+
+    /// Returns the token of the node registered under the given key, or `None`
+    /// if there is no such node.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::KeyedArena;
+    ///
+    /// let mut arena = KeyedArena::new();
+    /// let root = arena.insert_root("/", "root").unwrap();
+    /// let usr = arena.insert_child(root, "/usr", "usr").unwrap();
+    ///
+    /// assert_eq!(arena.get_by_key(&"/usr"), Some(usr));
+    /// arena.arena_mut().uproot(usr);
+    /// assert!(arena.get_by_key(&"/usr").is_none());
+    /// ```
+    pub fn get_by_key(&self, key: &K) -> Option<Token> {
+        self.arena.resolve_stable(*self.keys.get(key)?)
+    }
+
+    /// Returns a reference to the inner arena.
+    pub fn arena(&self) -> &Arena<T> { &self.arena }
+
+    /// Returns a mutable reference to the inner arena. Keys keep track of their
+    /// nodes through any change made to the arena. The key of a node removed
+    /// through the returned reference stops resolving right away, but its entry
+    /// is only dropped on a later insertion or call to [`retain_live`].
+    ///
+    /// [`retain_live`]: struct.KeyedArena.html#method.retain_live
+    pub fn arena_mut(&mut self) -> &mut Arena<T> { &mut self.arena }
+
+    /// Consumes the keyed arena and returns the inner arena.
+    pub fn into_arena(self) -> Arena<T> { self.arena }
+
+    /// Drops the keys whose nodes have been removed from the inner arena. This
+    /// also happens on its own once such keys pile up as nodes are inserted,
+    /// so calling it is only needed to release their memory early.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::KeyedArena;
+    ///
+    /// let mut arena = KeyedArena::new();
+    /// let root = arena.insert_root("/", "root").unwrap();
+    /// let usr = arena.insert_child(root, "/usr", "usr").unwrap();
+    /// arena.arena_mut().uproot(usr);
+    ///
+    /// assert_eq!(arena.key_count(), 2);
+    /// arena.retain_live();
+    /// assert_eq!(arena.key_count(), 1);
+    /// ```
+    pub fn retain_live(&mut self) {
+        let arena = &self.arena;
+        self.keys.retain(|_, &mut id| arena.resolve_stable(id).is_some());
+    }
+
+    /// Returns the number of keys held by the keyed arena, including the keys
+    /// of removed nodes that have not been dropped yet (see [`retain_live`]).
+    ///
+    /// [`retain_live`]: struct.KeyedArena.html#method.retain_live
+    pub fn key_count(&self) -> usize { self.keys.len() }
+
+    fn register(&mut self, key: K, token: Token) {
+        // dead keys never outnumber live ones by much, which keeps the
+        // occasional sweep amortized over the insertions
+        if self.keys.len() >= 2 * self.arena.node_count() { self.retain_live() }
+        let id = self.arena.stable_id(token);
+        self.keys.insert(key, id);
+    }
+}
+
+impl<K, T> Default for KeyedArena<K, T> where K: Eq + Hash {
+    fn default() -> Self { KeyedArena::new() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn duplicate_keys() {
+        let mut arena = KeyedArena::new();
+        let root = arena.insert_root(String::from("/"), 0usize).unwrap();
+        let usr = arena.insert_child(root, String::from("/usr"), 1).unwrap();
+        arena.insert_child(usr, String::from("/usr/bin"), 2).unwrap();
+
+        assert_eq!(arena.insert_root(String::from("/"), 3), Err(Error::DuplicateKey));
+        assert_eq!(arena.insert_child(root, String::from("/usr/bin"), 3),
+                   Err(Error::DuplicateKey));
+        assert_eq!(arena.arena().node_count(), 3);
+        assert_eq!(arena.arena()[usr].data, 1);
+
+        let (_, foreign) = Arena::with_data(0usize);
+        assert_eq!(arena.insert_child(foreign, String::from("/etc"), 3),
+                   Err(Error::InvalidToken));
+        assert!(arena.get_by_key(&String::from("/etc")).is_none());
+    }
+
+    #[test]
+    fn lookup_after_structural_changes() {
+        let mut arena = KeyedArena::new();
+        let root = arena.insert_root("/", 0usize).unwrap();
+        let usr = arena.insert_child(root, "/usr", 1).unwrap();
+        let bin = arena.insert_child(usr, "/usr/bin", 2).unwrap();
+        let etc = arena.insert_child(root, "/etc", 3).unwrap();
+
+        // moving nodes around
+        bin.detach(arena.arena_mut());
+        root.append_node(arena.arena_mut(), bin).unwrap();
+        etc.rotate_up(arena.arena_mut()).unwrap();
+        assert_eq!(arena.get_by_key(&"/usr/bin"), Some(bin));
+        assert_eq!(arena.get_by_key(&"/etc"), Some(etc));
+        assert_eq!(arena.get_by_key(&"/"), Some(root));
+
+        // removing nodes frees their keys, even if the slots are reused
+        arena.arena_mut().remove(usr);
+        assert!(arena.get_by_key(&"/usr").is_none());
+        let other = arena.arena_mut().new_node(4);
+        assert_eq!(other, usr);
+        assert!(arena.get_by_key(&"/usr").is_none());
+        let usr = arena.insert_child(root, "/usr", 5).unwrap();
+        assert_eq!(arena.get_by_key(&"/usr"), Some(usr));
+        assert_eq!(arena.into_arena()[usr].data, 5);
+    }
+
+    #[test]
+    fn stale_keys_are_dropped() {
+        let mut arena = KeyedArena::new();
+        let root = arena.insert_root(0usize, 0usize).unwrap();
+        for i in 1..1000 {
+            let child = arena.insert_child(root, i, i).unwrap();
+            arena.arena_mut().uproot(child);
+            assert!(arena.key_count() <= 2 * arena.arena().node_count() + 2);
+        }
+        assert_eq!(arena.get_by_key(&0), Some(root));
+        assert!(arena.get_by_key(&999).is_none());
+
+        let child = arena.insert_child(root, 1000, 1000).unwrap();
+        arena.arena_mut().remove(child);
+        arena.retain_live();
+        assert_eq!(arena.key_count(), 1);
+        assert_eq!(arena.get_by_key(&0), Some(root));
+
+        // keys of live nodes survive the sweeps
+        let children: Vec<_> = (1..10)
+            .map(|i| arena.insert_child(root, i, i).unwrap())
+            .collect();
+        arena.retain_live();
+        assert_eq!(arena.key_count(), 10);
+        for (i, &child) in (1..10).zip(&children) {
+            assert_eq!(arena.get_by_key(&i), Some(child));
+        }
+    }
+}
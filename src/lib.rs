@@ -146,9 +146,9 @@
 //! assert_eq!(arena[lang2].data, "Not romantic enough");
 //! ```
 //!
-//! To remove a single node from the arena, use the [`remove`] method. This will
-//! detach all the children of the node from the tree (but not remove them from
-//! memory).
+//! To remove a single node from the arena, use the [`remove`] method. The
+//! children of the node (along with their descendants) will take its place in
+//! the tree.
 //! ```
 //! use atree::Arena;
 //! use atree::iter::TraversalOrder;
@@ -163,21 +163,20 @@
 //! let scots = west.append(&mut arena, "Scots");
 //! let english = west.append(&mut arena, "English");
 //!
-//! // detach the west branch from the main tree
+//! // remove the west node from the tree
 //! let west_children = arena.remove(west);
 //!
-//! // the west branch is gone from the original tree
+//! // its children take its place in the tree
 //! let mut iter = root.subtree(&arena, TraversalOrder::Pre)
 //!     .map(|x| x.data);
 //! assert_eq!(iter.next(), Some("Indo-European"));
 //! assert_eq!(iter.next(), Some("Germanic"));
-//! assert!(iter.next().is_none());
-//!
-//! // its children are still areound
-//! let mut iter = west_children.iter().map(|&t| arena[t].data);
 //! assert_eq!(iter.next(), Some("Scots"));
 //! assert_eq!(iter.next(), Some("English"));
 //! assert!(iter.next().is_none());
+//!
+//! // the tokens of the children are returned
+//! assert_eq!(&west_children[..], &[scots, english]);
 //! ```
 //!
 //! To uproot a tree from the arena, call the [`uproot`] method on the arena.
@@ -218,17 +217,35 @@ extern crate serde;
 
 mod alloc;
 mod arena;
+mod builder;
+mod cursor;
+mod keyed;
 pub mod iter;
 mod node;
 mod token;
 
-pub use token::Token;
-pub use arena::Arena;
+pub use token::{StableId, Token};
+pub use arena::{Arena, ArenaStats, GrowthPolicy, IntegrityError, TreeDiff};
+pub use builder::ArenaBuilder;
+pub use cursor::{Cursor, CursorMut, Zipper};
+pub use keyed::KeyedArena;
 pub use node::Node;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 /// The Error type
 pub enum Error {
     /// Not a root node error
-    NotARootNode
+    NotARootNode,
+    /// Invalid token error
+    InvalidToken,
+    /// Same token error (for operations that require two distinct nodes)
+    SameToken,
+    /// No parent error (for operations that require a non-root node)
+    NoParent,
+    /// Duplicate key error (for keyed arenas)
+    DuplicateKey,
+    /// Cycle error (for operations that would make a node its own ancestor)
+    WouldCycle,
+    /// Invalid depth error (for trees built from lists of depths)
+    InvalidDepth
 }
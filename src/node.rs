@@ -1,6 +1,6 @@
 // mutable iterators are impossible for Node<T> due to borrow checking rules
 use crate::arena::Arena;
-use crate::token::Token;
+use crate::token::{StableId, Token};
 use crate::iter::*;
 
 /// A node holds data in the arena. `Node<T>` can be accessed by indexing
@@ -25,6 +25,10 @@ pub struct Node<T> {
     pub (crate) next_sibling: Option<Token>,
     /// The "first child" node.
     pub (crate) first_child: Option<Token>,
+    /// The "last child" node.
+    pub (crate) last_child: Option<Token>,
+    /// The stable id of the node, if one has been issued.
+    pub (crate) stable_id: Option<StableId>,
 }
 
 impl<T> Node<T> {
@@ -76,6 +80,23 @@ impl<T> Node<T> {
     /// ```
     pub fn first_child(&self) -> Option<Token> { self.first_child }
 
+    /// Returns the last child of the node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data("Germanic");
+    /// root_token.append(&mut arena, "English");
+    /// let swedish = root_token.append(&mut arena, "Swedish");
+    ///
+    /// let root = &arena[root_token];
+    /// assert_eq!(root.last_child(), Some(swedish));
+    /// ```
+    pub fn last_child(&self) -> Option<Token> { self.last_child }
+
     /// Returns the parent of the node.
     ///
     /// # Examples
@@ -387,8 +408,42 @@ impl<T> Node<T> {
         self.token.subtree(arena, order)
     }
 
-    pub (crate) fn remove_descendants(&mut self, arena: &mut Arena<T>) {
-        self.token.remove_descendants(arena)
+    /// Returns the token of the root of the tree the node belongs to. Returns
+    /// the token of the node itself if it is already a root.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    ///
+    /// let germanic_node = &arena[germanic];
+    /// assert_eq!(germanic_node.root(&arena), root_token);
+    /// ```
+    pub fn root(&self, arena: &Arena<T>) -> Token {
+        self.token.root(arena)
+    }
+
+    /// Returns the number of children of the node.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// root_token.append(&mut arena, "Germanic");
+    /// root_token.append(&mut arena, "Romance");
+    ///
+    /// let root = &arena[root_token];
+    /// assert_eq!(root.children_count(&arena), 2);
+    /// ```
+    pub fn children_count(&self, arena: &Arena<T>) -> usize {
+        self.token.children_count(arena)
     }
 }
 
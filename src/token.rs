@@ -1,32 +1,50 @@
 #![allow(clippy::match_bool)]
-use std::collections::VecDeque;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
 use std::marker::PhantomData;
+use std::mem;
 use std::num::NonZeroUsize;
-use std::mem::MaybeUninit;
 
 use crate::Error;
 use crate::iter::*;
 use crate::node::Node;
 use crate::arena::Arena;
 
-/// A `Token` is a handle to a node in the arena.
+/// A `Token` is a handle to a node in the arena. A token only ever refers to
+/// nodes of the arena that issued it (and of clones of that arena); using it
+/// with any other arena is treated the same as using an invalid token.
 #[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
 pub struct Token {
-    pub (crate) index: NonZeroUsize
+    pub (crate) index: NonZeroUsize,
+    pub (crate) arena_id: u64
 }
 
+/// A `StableId` is a handle to a node that, unlike a [`Token`], is never
+/// reused for another node once the node it refers to is removed. See
+/// [`stable_id`] for more.
+///
+/// [`Token`]: struct.Token.html
+/// [`stable_id`]: struct.Arena.html#method.stable_id
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub struct StableId(pub (crate) u64);
+
 fn node_operation<T>(
     self_token: Token,
     arena: &mut Arena<T>,
     other_token: Token,
-    func: fn(Token, &mut Arena<T>, T) -> Token
+    func: fn(Token, &mut Arena<T>, Token)
 ) -> Result<(), Error> {
-    // only a placeholder to get around some trait requirements so I can
-    // reuse code. The uninitialized data will be removed so no risk here.
-    let dummy_data: T = unsafe { MaybeUninit::zeroed().assume_init() };
-    let token = func(self_token, arena, dummy_data);
-    token.replace_node(arena, other_token)?;
-    arena.remove(token);  // remove uninitialized data
+    // check that the other node is really a root node of its own
+    match arena.get(other_token) {
+        None => panic!("Invalid token"),
+        Some(node) => match (node.parent,
+                             node.previous_sibling,
+                             node.next_sibling) {
+            (None, None, None) => (),
+            _ => return Err(Error::NotARootNode)
+        }
+    }
+    func(self_token, arena, other_token);
     Ok(())
 }
 
@@ -67,32 +85,115 @@ impl Token {
     /// assert_eq!(subtree.next().unwrap().data, "Romance");
     /// ```
     pub fn append<T>(self, arena: &mut Arena<T>, data: T) -> Token {
-        let new_node_token = arena.allocator.head();
-        let previous_sibling = match self.children_mut(arena).last() {
-            None => {
-                // children_mut will have checked indexability so this will not
-                // fail
-                arena[self].first_child = Some(new_node_token);
-                None
-            },
-            Some(last_child) => {
-                last_child.next_sibling = Some(new_node_token);
-                Some(last_child.token)
-            }
-        };
+        match self.try_append(arena, data) {
+            Ok(token) => token,
+            Err(_) => panic!("Invalid token")
+        }
+    }
+
+    /// Creates a new node with the given data and append to the given node.
+    /// Unlike [`append`], this returns an error instead of panicking if the
+    /// token is invalid, in which case the arena is left untouched.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `Err(Error::InvalidToken)` if the token does not correspond to a
+    /// node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::{Arena, Error};
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.try_append(&mut arena, "Germanic").unwrap();
+    /// assert_eq!(arena[germanic].parent(), Some(root_token));
+    ///
+    /// arena.uproot(germanic);
+    /// assert_eq!(germanic.try_append(&mut arena, "English"),
+    ///            Err(Error::InvalidToken));
+    /// ```
+    ///
+    /// [`append`]: struct.Token.html#method.append
+    pub fn try_append<T>(self, arena: &mut Arena<T>, data: T)
+        -> Result<Token, Error> {
+        if arena.get(self).is_none() { return Err(Error::InvalidToken) }
+        let new_node_token = arena.new_node(data);
+        self.link_append(arena, new_node_token);
+        Ok(new_node_token)
+    }
 
-        let node = Node {
-            data,
-            token: new_node_token,
-            parent: Some(self),
-            previous_sibling,
-            next_sibling: None,
-            first_child: None
+    /// Creates a new node with the given data and prepends it to the given
+    /// node, making it the first child of the node.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// root_token.append(&mut arena, "Germanic");
+    /// let romance = root_token.prepend(&mut arena, "Romance");
+    ///
+    /// assert_eq!(arena[root_token].first_child(), Some(romance));
+    /// let children: Vec<_> = root_token.children(&arena).map(|x| x.data).collect();
+    /// assert_eq!(&children[..], &["Romance", "Germanic"]);
+    /// ```
+    pub fn prepend<T>(self, arena: &mut Arena<T>, data: T) -> Token {
+        let first_child = match arena.get(self) {
+            Some(node) => node.first_child,
+            None => panic!("Invalid token")
         };
-        arena.set(new_node_token, node);
+        let new_node_token = arena.new_node(data);
+        match first_child {
+            Some(child) => child.link_before(arena, new_node_token),
+            None => self.link_append(arena, new_node_token)
+        }
         new_node_token
     }
 
+    /// Creates a new node with the given data and inserts it as a child of the
+    /// given node at position `index` (counting from 0), shifting the child
+    /// currently at that position and all the children after it back by one.
+    /// The node is appended as the last child if `index` is not less than the
+    /// number of children.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// root_token.append(&mut arena, "Romance");
+    /// root_token.append(&mut arena, "Slavic");
+    /// let germanic = root_token.insert_child_at(&mut arena, 1, "Germanic");
+    ///
+    /// assert_eq!(root_token.nth_child(&arena, 1), Some(germanic));
+    /// let children: Vec<_> = root_token.children(&arena).map(|x| x.data).collect();
+    /// assert_eq!(&children[..], &["Romance", "Germanic", "Slavic"]);
+    /// ```
+    pub fn insert_child_at<T>(self, arena: &mut Arena<T>, index: usize, data: T)
+        -> Token {
+        match index {
+            0 => self.prepend(arena, data),
+            _ => match self.nth_child(arena, index) {
+                Some(child) => child.insert_before(arena, data),
+                None => self.append(arena, data)
+            }
+        }
+    }
+
     /// Creates a new node with the given data and sets as the previous sibling
     /// of the current node.
     ///
@@ -124,42 +225,14 @@ impl Token {
     ///            &subtree[..]);
     /// ```
     pub fn insert_before<T>(self, arena: &mut Arena<T>, data: T) -> Token {
-        let new_node_token = arena.allocator.head();
-        let (self_parent, self_previous_sibling) = match arena.get(self) {
+        match arena.get(self) {
             None => panic!("Invalid token"),
-            Some(node) => (node.parent, node.previous_sibling)
-        };
-        arena[self].previous_sibling = Some(new_node_token);  // already checked
-        let previous_sibling = match self_previous_sibling {
-            Some(sibling) => match arena.get_mut(sibling) {
-                None => panic!("Corrupt arena"),
-                Some(ref mut node) => {
-                    node.next_sibling = Some(new_node_token);
-                    Some(sibling)
-                }
-            },
-            None => match self_parent {
-                None => panic!("Cannot insert as the previous sibling of the \
-                                root node"),
-                Some(p) => match arena.get_mut(p) {
-                    None => panic!("Corrupt arena"),
-                    Some(ref mut node) => {
-                        node.first_child = Some(new_node_token);
-                        None
-                    }
-                }
+            Some(node) => if node.parent.is_none() {
+                panic!("Cannot insert as the previous sibling of the root node")
             }
-        };
-
-        let node = Node {
-            data,
-            token: new_node_token,
-            parent: self_parent,
-            previous_sibling,
-            next_sibling: Some(self),
-            first_child: None
-        };
-        arena.set(new_node_token, node);
+        }
+        let new_node_token = arena.new_node(data);
+        self.link_before(arena, new_node_token);
         new_node_token
     }
 
@@ -207,7 +280,7 @@ impl Token {
     /// ```
     pub fn insert_node_after<T>(self, arena: &mut Arena<T>, other: Token)
         -> Result<(), Error> {
-        node_operation(self, arena, other, Token::insert_after)
+        node_operation(self, arena, other, Token::link_after)
     }
 
     /// Set a node in the arena as the previous sibling of the given node.
@@ -254,7 +327,7 @@ impl Token {
     /// ```
     pub fn insert_node_before<T>(self, arena: &mut Arena<T>, other: Token)
         -> Result<(), Error> {
-        node_operation(self, arena, other, Token::insert_before)
+        node_operation(self, arena, other, Token::link_before)
     }
 
     /// Creates a new node with the given data and sets as the next sibling of
@@ -288,32 +361,9 @@ impl Token {
     ///            &subtree[..]);
     /// ```
     pub fn insert_after<T>(self, arena: &mut Arena<T>, data: T) -> Token {
-        let new_node_token = arena.allocator.head();
-        let (self_parent, self_next_sibling) = match arena.get(self) {
-            None => panic!("Invalid token"),
-            Some(node) => (node.parent, node.next_sibling)
-        };
-        arena[self].next_sibling = Some(new_node_token);  // already checked
-        let next_sibling = match self_next_sibling {
-            None => None,
-            Some(sibling) => match arena.get_mut(sibling) {
-                None => panic!("Corrupt arena"),
-                Some(ref mut node) => {
-                    node.previous_sibling = Some(new_node_token);
-                    Some(sibling)
-                }
-            },
-        };
-
-        let node = Node {
-            data,
-            token: new_node_token,
-            parent: self_parent,
-            previous_sibling: Some(self),
-            next_sibling,
-            first_child: None
-        };
-        arena.set(new_node_token, node);
+        if arena.get(self).is_none() { panic!("Invalid token") }
+        let new_node_token = arena.new_node(data);
+        self.link_after(arena, new_node_token);
         new_node_token
     }
 
@@ -324,7 +374,7 @@ impl Token {
     ///
     /// **Note**: for performance reasons, this operation does not check whether
     /// the "self" node is in fact a descendant of the other tree. A cyclic
-    /// graph may result.
+    /// graph may result. Use [`try_append_node`] for a checked version.
     ///
     /// # Panics:
     ///
@@ -368,9 +418,94 @@ impl Token {
     /// ```
     ///
     /// [`copy_and_append_subtree`]: struct.Arena.html#method.copy_and_append_subtree
+    /// [`try_append_node`]: struct.Token.html#method.try_append_node
     pub fn append_node<T>(self, arena: &mut Arena<T>, other: Self)
         -> Result<(), Error> {
-        node_operation(self, arena, other, Token::append)
+        node_operation(self, arena, other, Token::link_append)
+    }
+
+    /// Attaches a different tree in the arena to a node, like [`append_node`],
+    /// but refuses to do so if the current node belongs to the other tree.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `Err(Error::WouldCycle)` if the current node is the other node
+    /// or one of its descendants, and `Err(Error::NotARootNode)` if the other
+    /// node is not a root node. The arena is left untouched in either case.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the tokens do not correspond to nodes in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::{Arena, Error};
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root) = Arena::with_data(root_data);
+    /// let germanic = arena.new_node("Germanic");
+    /// let west = germanic.append(&mut arena, "West");
+    ///
+    /// assert_eq!(west.try_append_node(&mut arena, germanic),
+    ///            Err(Error::WouldCycle));
+    /// assert_eq!(root.try_append_node(&mut arena, germanic), Ok(()));
+    /// assert_eq!(arena[germanic].parent(), Some(root));
+    /// ```
+    ///
+    /// [`append_node`]: struct.Token.html#method.append_node
+    pub fn try_append_node<T>(self, arena: &mut Arena<T>, other: Token)
+        -> Result<(), Error> {
+        match self == other || other.is_ancestor_of(self, arena) {
+            true => Err(Error::WouldCycle),
+            false => self.append_node(arena, other)
+        }
+    }
+
+    /// Moves the given node along with its descendants to the end of the
+    /// children of `new_parent`, whether or not the node currently has a
+    /// parent. The nodes keep their tokens, and unlike
+    /// [`copy_and_append_subtree`], no data is cloned.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `Err(Error::WouldCycle)` if `new_parent` is the given node or
+    /// one of its descendants, in which case the arena is left untouched.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the tokens do not correspond to nodes in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::{Arena, Error};
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root) = Arena::with_data(root_data);
+    /// let germanic = root.append(&mut arena, "Germanic");
+    /// let romance = root.append(&mut arena, "Romance");
+    /// let english = romance.append(&mut arena, "English");
+    ///
+    /// assert_eq!(english.move_subtree(&mut arena, germanic), Ok(()));
+    /// assert_eq!(arena[english].parent(), Some(germanic));
+    /// assert!(arena[romance].first_child().is_none());
+    ///
+    /// assert_eq!(germanic.move_subtree(&mut arena, english),
+    ///            Err(Error::WouldCycle));
+    /// ```
+    ///
+    /// [`copy_and_append_subtree`]: struct.Arena.html#method.copy_and_append_subtree
+    pub fn move_subtree<T>(self, arena: &mut Arena<T>, new_parent: Token)
+        -> Result<(), Error> {
+        match self == new_parent || self.is_ancestor_of(new_parent, arena) {
+            true => Err(Error::WouldCycle),
+            false => {
+                self.detach(arena);
+                new_parent.link_append(arena, self);
+                Ok(())
+            }
+        }
     }
 
     /// Detaches the given node and its descendants into its own tree while
@@ -443,12 +578,171 @@ impl Token {
             }
         }
 
-        if let Some(token) = next_sibling {
-            match arena.get_mut(token) {
+        match next_sibling {
+            Some(token) => match arena.get_mut(token) {
                 None => panic!("Corrupt arena"),
                 Some(node) => node.previous_sibling = previous_sibling
+            },
+            None => if let Some(token) = parent {
+                match arena.get_mut(token) {
+                    None => panic!("Corrupt arena"),
+                    Some(n) => n.last_child = previous_sibling
+                }
+            }
+        }
+    }
+
+    /// Detaches all the children of the given node (along with their
+    /// descendants) into their own trees while keeping them in the same arena.
+    /// Returns the tokens of the detached children in the order of insertion.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Germanic";
+    /// let (mut arena, root) = Arena::with_data(root_data);
+    ///
+    /// let west = root.append(&mut arena, "West");
+    /// let north = root.append(&mut arena, "North");
+    /// let english = west.append(&mut arena, "English");
+    ///
+    /// let children = root.detach_children(&mut arena);
+    /// assert_eq!(&children[..], &[west, north]);
+    /// assert!(root.is_leaf(&arena));
+    /// for &child in &children {
+    ///     let node = &arena[child];
+    ///     assert!(node.parent().is_none());
+    ///     assert!(node.previous_sibling().is_none());
+    ///     assert!(node.next_sibling().is_none());
+    /// }
+    ///
+    /// // descendants stay with the detached children
+    /// assert_eq!(arena[english].parent(), Some(west));
+    /// ```
+    pub fn detach_children<T>(self, arena: &mut Arena<T>) -> Vec<Token> {
+        let children: Vec<Token> = self.children_tokens(arena).collect();
+        let node = &mut arena[self];  // indexability has been checked
+        node.first_child = None;
+        node.last_child = None;
+        for &child in &children {
+            match arena.get_mut(child) {
+                None => panic!("Corrupt arena"),
+                Some(node) => {
+                    node.parent = None;
+                    node.previous_sibling = None;
+                    node.next_sibling = None;
+                }
+            }
+        }
+        children
+    }
+
+    /// Reorders the children of the given node with a comparator function on
+    /// the child nodes. The sort is stable, and the descendants of the children
+    /// move along with them.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// root_token.append(&mut arena, "Slavic");
+    /// root_token.append(&mut arena, "Germanic");
+    /// root_token.append(&mut arena, "Romance");
+    ///
+    /// root_token.sort_children_by(&mut arena, |a, b| a.data.cmp(b.data));
+    /// let children: Vec<_> = root_token.children(&arena).map(|x| x.data).collect();
+    /// assert_eq!(&children[..], &["Germanic", "Romance", "Slavic"]);
+    /// ```
+    pub fn sort_children_by<T, F>(self, arena: &mut Arena<T>, mut cmp: F)
+        where F: FnMut(&Node<T>, &Node<T>) -> Ordering {
+        let mut children = self.detach_children(arena);
+        children.sort_by(|&a, &b| cmp(&arena[a], &arena[b]));
+        for child in children {
+            self.link_append(arena, child);
+        }
+    }
+
+    /// Reorders the children of the given node by the key extracted from each
+    /// child node with the given function. The sort is stable, and the
+    /// descendants of the children move along with them.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// root_token.append(&mut arena, "Germanic");
+    /// root_token.append(&mut arena, "Celtic");
+    /// root_token.append(&mut arena, "Italic");
+    ///
+    /// root_token.sort_children_by_key(&mut arena, |node| node.data.len());
+    /// let children: Vec<_> = root_token.children(&arena).map(|x| x.data).collect();
+    /// assert_eq!(&children[..], &["Celtic", "Italic", "Germanic"]);
+    /// ```
+    pub fn sort_children_by_key<T, K, F>(self, arena: &mut Arena<T>, mut f: F)
+        where F: FnMut(&Node<T>) -> K,
+              K: Ord {
+        self.sort_children_by(arena, |a, b| f(a).cmp(&f(b)))
+    }
+
+    /// Removes the given node from the arena and puts its children (along with
+    /// their descendants) in its place among its siblings, in order. Returns
+    /// the tokens of the children. If the node is a root node, its children
+    /// become standalone trees in the arena instead.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root) = Arena::with_data(root_data);
+    ///
+    /// let celtic = root.append(&mut arena, "Celtic");
+    /// let germanic = root.append(&mut arena, "Germanic");
+    /// let slavic = root.append(&mut arena, "Slavic");
+    /// let west = germanic.append(&mut arena, "West");
+    /// let north = germanic.append(&mut arena, "North");
+    ///
+    /// let promoted = germanic.remove_and_promote_children(&mut arena);
+    /// assert_eq!(&promoted[..], &[west, north]);
+    ///
+    /// let children: Vec<_> = root.children(&arena).map(|x| x.data).collect();
+    /// assert_eq!(&["Celtic", "West", "North", "Slavic"], &children[..]);
+    /// assert!(arena.get(germanic).is_none());
+    /// ```
+    pub fn remove_and_promote_children<T>(self, arena: &mut Arena<T>)
+        -> Vec<Token> {
+        let children = self.detach_children(arena);
+        if arena[self].parent.is_some() {  // indexability has been checked
+            for &child in &children {
+                self.link_before(arena, child);
             }
         }
+        self.detach(arena);
+        arena.free(self);
+        children
     }
 
     /// Replace the subtree of self with the subtree of other. Does not remove
@@ -549,10 +843,16 @@ impl Token {
             }
         }
 
-        if let Some(sibling) = next_sibling {
-            match arena.get_mut(sibling) {
+        match next_sibling {
+            Some(sibling) => match arena.get_mut(sibling) {
                 None => panic!("Corrupt arena"),
                 Some(node) => node.previous_sibling = Some(other)
+            },
+            None => if let Some(p) = parent {
+                match arena.get_mut(p) {
+                    None => panic!("Corrupt arena"),
+                    Some(node) => node.last_child = Some(other)
+                }
             }
         }
 
@@ -616,11 +916,23 @@ impl Token {
     /// ```
     pub fn preceding_siblings_tokens<'a, T>(self, arena: &'a Arena<T>)
         -> PrecedingSiblingTokens<'a, T> {
-        let previous_sibling = match arena.get(self) {
-            Some(n) => n.previous_sibling,
+        let (previous_sibling, parent) = match arena.get(self) {
+            Some(n) => (n.previous_sibling, n.parent),
             None => panic!("Invalid token")
         };
-        PrecedingSiblingTokens { arena, node_token: previous_sibling }
+        let first_sibling = match (previous_sibling, parent) {
+            (None, _) => None,
+            (Some(_), Some(parent)) => arena[parent].first_child,
+            (Some(mut sibling), None) => {
+                while let Some(next) = arena[sibling].previous_sibling { sibling = next }
+                Some(sibling)
+            }
+        };
+        PrecedingSiblingTokens {
+            arena,
+            node_token: previous_sibling,
+            back_token: first_sibling
+        }
     }
 
     /// Returns an iterator of tokens of siblings following the current node.
@@ -649,14 +961,28 @@ impl Token {
     /// ```
     pub fn following_siblings_tokens<'a, T>(self, arena: &'a Arena<T>)
         -> FollowingSiblingTokens<'a, T> {
-        let next_sibling = match arena.get(self) {
-            Some(n) => n.next_sibling,
+        let (next_sibling, parent) = match arena.get(self) {
+            Some(n) => (n.next_sibling, n.parent),
             None => panic!("Invalid token")
         };
-        FollowingSiblingTokens { arena, node_token: next_sibling }
+        let last_sibling = match (next_sibling, parent) {
+            (None, _) => None,
+            (Some(_), Some(parent)) => arena[parent].last_child,
+            (Some(mut sibling), None) => {
+                while let Some(next) = arena[sibling].next_sibling { sibling = next }
+                Some(sibling)
+            }
+        };
+        FollowingSiblingTokens {
+            arena,
+            node_token: next_sibling,
+            back_token: last_sibling
+        }
     }
 
     /// Returns an iterator of tokens of child nodes in the order of insertion.
+    /// The children are counted when the iterator is created, which takes time
+    /// linear in the number of children, so that its length is known.
     ///
     /// # Panics:
     ///
@@ -684,11 +1010,16 @@ impl Token {
     /// ```
     pub fn children_tokens<'a, T>(self, arena: &'a Arena<T>)
         -> ChildrenTokens<'a, T> {
-        let first_child = match arena.get(self) {
-            Some(n) => n.first_child,
+        let (first_child, last_child) = match arena.get(self) {
+            Some(n) => (n.first_child, n.last_child),
             None => panic!("Invalid token")
         };
-        ChildrenTokens { arena, node_token: first_child }
+        ChildrenTokens {
+            arena,
+            node_token: first_child,
+            back_token: last_child,
+            len: self.children_count(arena)
+        }
     }
 
     /// Returns an iterator of references of ancestor nodes.
@@ -836,13 +1167,20 @@ impl Token {
     /// assert_eq!(ancestors.next().unwrap().data, 4usize);
     /// assert_eq!(ancestors.next().unwrap().data, 3usize);
     /// assert!(ancestors.next().is_none());
+    ///
+    /// // the node itself is left alone
+    /// assert_eq!(arena[ggreat_grandchild_token].data, 5usize);
     /// ```
     pub fn ancestors_mut<'a, T>(self, arena: &'a mut Arena<T>)
         -> AncestorsMut<'a, T> {
+        let parent = match arena.get(self) {
+            Some(n) => n.parent,
+            None => panic!("Invalid token")
+        };
         AncestorsMut {
             arena: arena as *mut Arena<T>,
-            node_token: Some(self),
-            marker: PhantomData::default()
+            node_token: parent,
+            marker: PhantomData
         }
     }
 
@@ -886,7 +1224,7 @@ impl Token {
         FollowingSiblingsMut {
             arena: arena as *mut Arena<T>,
             node_token: next_sibling,
-            marker: PhantomData::default()
+            marker: PhantomData
         }
     }
 
@@ -930,7 +1268,7 @@ impl Token {
         PrecedingSiblingsMut {
             arena: arena as *mut Arena<T>,
             node_token: previous_sibling,
-            marker: PhantomData::default()
+            marker: PhantomData
         }
     }
 
@@ -976,7 +1314,7 @@ impl Token {
         ChildrenMut {
             arena: arena as *mut Arena<T>,
             node_token: first_child,
-            marker: PhantomData::default()
+            marker: PhantomData
         }
     }
 
@@ -1141,281 +1479,3973 @@ impl Token {
         SubtreeMut {
             arena: arena as *mut Arena<T>,
             iter: self.subtree_tokens(arena, order),
-            marker: PhantomData::default()
+            marker: PhantomData
         }
     }
 
-    /// Removes all descendants of the current node.
-    pub (crate) fn remove_descendants<T>(self, arena: &mut Arena<T>) {
-        // This will not silently fail since postorder_next will panic if self
-        // isn't valid.  This won't do anything if self has no descendants, but
+    /// Folds over the nodes of the subtree in the given order. This is
+    /// equivalent to calling `fold` on [`subtree`], without having to pass the
+    /// arena around.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    /// use atree::iter::TraversalOrder;
+    ///
+    /// let root_data = 1usize;
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    ///
+    /// let child = root_token.append(&mut arena, 2usize);
+    /// root_token.append(&mut arena, 3usize);
+    /// child.append(&mut arena, 4usize);
+    ///
+    /// let sum = root_token.fold_subtree(&arena, 0, TraversalOrder::Pre,
+    ///                                   |acc, node| acc + node.data);
+    /// assert_eq!(sum, 10);
+    /// ```
+    ///
+    /// [`subtree`]: struct.Token.html#method.subtree
+    pub fn fold_subtree<T, B, F>(self, arena: &Arena<T>, init: B,
+                                 order: TraversalOrder, f: F) -> B
+        where F: FnMut(B, &Node<T>) -> B {
+        self.subtree(arena, order).fold(init, f)
+    }
+
+    /// Computes a value for every node of the subtree from its data and the
+    /// values already computed for its children (in the order of insertion),
+    /// and returns the value computed for the current node.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = 1usize;
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    ///
+    /// let child = root_token.append(&mut arena, 2usize);
+    /// root_token.append(&mut arena, 3usize);
+    /// child.append(&mut arena, 4usize);
+    ///
+    /// // height of the tree
+    /// let height = root_token.reduce_bottom_up(&arena, |_, children: &[usize]| {
+    ///     children.iter().max().map_or(0, |h| h + 1)
+    /// });
+    /// assert_eq!(height, 2);
+    /// ```
+    pub fn reduce_bottom_up<T, R, F>(self, arena: &Arena<T>, mut f: F) -> R
+        where F: FnMut(&Node<T>, &[R]) -> R {
+        // In post-order, the values of the children of a node are always the
+        // last ones on the stack by the time we get to the node.
+        let mut stack: Vec<R> = Vec::new();
+        for node in self.subtree(arena, TraversalOrder::Post) {
+            let nchildren = node.children_tokens(arena).count();
+            let children_values = stack.split_off(stack.len() - nchildren);
+            stack.push(f(node, &children_values));
+        }
+        match stack.pop() {
+            Some(value) => value,
+            None => panic!("Invalid token")
+        }
+    }
+
+    /// Returns an iterator of tokens of the descendants of the given node, that
+    /// is the subtree nodes excluding the node itself.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    /// use atree::iter::TraversalOrder;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    ///
+    /// let first_child = root_token.append(&mut arena, "Romance");
+    /// let second_child = root_token.append(&mut arena, "Germanic");
+    /// let grandchild = second_child.append(&mut arena, "English");
+    ///
+    /// let mut descendants = root_token.descendants_tokens(&arena, TraversalOrder::Post);
+    /// assert_eq!(descendants.next(), Some(first_child));
+    /// assert_eq!(descendants.next(), Some(grandchild));
+    /// assert_eq!(descendants.next(), Some(second_child));
+    /// assert!(descendants.next().is_none());
+    /// ```
+    pub fn descendants_tokens<'a, T>(self, arena: &'a Arena<T>,
+                                     order: TraversalOrder)
+        -> impl Iterator<Item = Token> + 'a {
+        self.subtree_tokens(arena, order).filter(move |&token| token != self)
+    }
+
+    /// Returns an iterator of references of subtree nodes of the given node,
+    /// each paired with the token of its parent. The parent of the node the
+    /// traversal starts from is always reported as `None`.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    /// use atree::iter::TraversalOrder;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    ///
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    ///
+    /// let pairs: Vec<_> = root_token
+    ///     .subtree_with_parents(&arena, TraversalOrder::Pre)
+    ///     .map(|(parent, node)| (parent, node.data))
+    ///     .collect();
+    /// assert_eq!(&[(None, "Indo-European"),
+    ///              (Some(root_token), "Germanic"),
+    ///              (Some(germanic), "English")], &pairs[..]);
+    /// ```
+    pub fn subtree_with_parents<'a, T>(self, arena: &'a Arena<T>,
+                                       order: TraversalOrder)
+        -> impl Iterator<Item = (Option<Token>, &'a Node<T>)> + 'a {
+        self.subtree(arena, order).map(move |node| match node.token == self {
+            true => (None, node),
+            false => (node.parent, node)
+        })
+    }
+
+    /// Returns an iterator of mutable references of the leaves of the subtree
+    /// of the given node, in pre-order.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    /// use atree::iter::TraversalOrder;
+    ///
+    /// let root_data = 1usize;
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    ///
+    /// let child = root_token.append(&mut arena, 2usize);
+    /// root_token.append(&mut arena, 3usize);
+    /// child.append(&mut arena, 4usize);
+    ///
+    /// for leaf in root_token.leaves_mut(&mut arena) {
+    ///     leaf.data *= 10;
+    /// }
+    ///
+    /// let data: Vec<_> = root_token.subtree(&arena, TraversalOrder::Pre)
+    ///     .map(|x| x.data)
+    ///     .collect();
+    /// assert_eq!(&[1, 2, 40, 30], &data[..]);
+    /// ```
+    pub fn leaves_mut<'a, T>(self, arena: &'a mut Arena<T>)
+        -> impl Iterator<Item = &'a mut Node<T>> + 'a {
+        self.subtree_mut(arena, TraversalOrder::Pre).filter(|node| node.is_leaf())
+    }
+
+    /// Searches the subtree of the given node in level-order and returns the
+    /// path (from the given node down to the match) to the first node that
+    /// satisfies the predicate, which is the shortest such path. Returns
+    /// `None` if no node matches.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    ///
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let west = germanic.append(&mut arena, "West");
+    /// let english = west.append(&mut arena, "English");
+    ///
+    /// let path = root_token.bfs_path_to(&arena, |x| x.data == "English");
+    /// assert_eq!(path, Some(vec![root_token, germanic, west, english]));
+    /// assert!(root_token.bfs_path_to(&arena, |x| x.data == "Latin").is_none());
+    /// ```
+    pub fn bfs_path_to<T, F>(self, arena: &Arena<T>, mut f: F)
+        -> Option<Vec<Token>> where F: FnMut(&Node<T>) -> bool {
+        let found = self.subtree(arena, TraversalOrder::Level)
+            .find(|node| f(node))?
+            .token;
+        let mut path = vec![found];
+        if found != self {
+            for token in found.ancestors_tokens(arena) {
+                path.push(token);
+                if token == self { break }
+            }
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Returns the token of the root of the tree the node belongs to. Returns
+    /// the token itself if the node is already a root.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    ///
+    /// assert_eq!(english.root(&arena), root_token);
+    /// assert_eq!(root_token.root(&arena), root_token);
+    /// ```
+    pub fn root<T>(self, arena: &Arena<T>) -> Token {
+        match arena.get(self) {
+            None => panic!("Invalid token"),
+            Some(_) => self.ancestors_tokens(arena).last().unwrap_or(self)
+        }
+    }
+
+    /// Creates a copy of the node as its immediate next sibling and returns the
+    /// token of the copy. Only the data of the node is copied: the copy is
+    /// created without any descendants. If the node is a root, the copy is
+    /// created as a free-standing node (a root of its own).
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// romance.append(&mut arena, "French");
+    /// root_token.append(&mut arena, "Germanic");
+    ///
+    /// let copy = romance.duplicate(&mut arena);
+    /// assert_eq!(arena[romance].next_sibling(), Some(copy));
+    /// assert!(arena[copy].is_leaf());
+    ///
+    /// let children: Vec<_> = root_token.children(&arena).map(|x| x.data)
+    ///     .collect();
+    /// assert_eq!(&children[..], &["Romance", "Romance", "Germanic"]);
+    ///
+    /// // duplicating a root creates a new tree
+    /// let root_copy = root_token.duplicate(&mut arena);
+    /// assert!(arena[root_copy].parent().is_none());
+    /// assert!(arena[root_token].next_sibling().is_none());
+    /// ```
+    pub fn duplicate<T: Clone>(self, arena: &mut Arena<T>) -> Token {
+        let (data, parent) = match arena.get(self) {
+            None => panic!("Invalid token"),
+            Some(node) => (node.data.clone(), node.parent)
+        };
+        let new_node_token = arena.new_node(data);
+        if parent.is_some() { self.link_after(arena, new_node_token) }
+        new_node_token
+    }
+
+    /// Returns the token of the last child of the node, or `None` if the node
+    /// is a leaf.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// root_token.append(&mut arena, "Romance");
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    ///
+    /// assert_eq!(root_token.last_child(&arena), Some(germanic));
+    /// assert!(germanic.last_child(&arena).is_none());
+    /// ```
+    pub fn last_child<T>(self, arena: &Arena<T>) -> Option<Token> {
+        match arena.get(self) {
+            None => panic!("Invalid token"),
+            Some(node) => node.last_child
+        }
+    }
+
+    /// Checks whether the two nodes are siblings, i.e. they are distinct nodes
+    /// that share the same parent. A node is not its own sibling and root nodes
+    /// are not siblings of anything.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the tokens do not correspond to nodes in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    ///
+    /// assert!(romance.is_sibling_of(germanic, &arena));
+    /// assert!(!romance.is_sibling_of(english, &arena));
+    /// assert!(!romance.is_sibling_of(romance, &arena));
+    /// ```
+    pub fn is_sibling_of<T>(self, other: Token, arena: &Arena<T>) -> bool {
+        match (arena.get(self), arena.get(other)) {
+            (Some(x), Some(y)) => self != other
+                && x.parent.is_some()
+                && x.parent == y.parent,
+            _ => panic!("Invalid token")
+        }
+    }
+
+    /// Removes the descendants of the node that satisfy the given predicate,
+    /// along with their own descendants. The predicate is not called on the
+    /// node itself nor on the descendants of removed nodes.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    /// use atree::iter::TraversalOrder;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "Gothic");
+    /// germanic.append(&mut arena, "English");
+    /// let italic = root_token.append(&mut arena, "Italic");
+    /// italic.append(&mut arena, "Latin");
+    ///
+    /// // remove the extinct languages
+    /// root_token.remove_descendants_where(&mut arena, |x| {
+    ///     x.data == "Gothic" || x.data == "Italic"
+    /// });
+    /// let subtree: Vec<_> = root_token.subtree(&arena, TraversalOrder::Pre)
+    ///     .map(|x| x.data)
+    ///     .collect();
+    /// assert_eq!(&subtree[..], &["Indo-European", "Germanic", "English"]);
+    /// ```
+    pub fn remove_descendants_where<T, F>(self, arena: &mut Arena<T>, mut f: F)
+        where F: FnMut(&Node<T>) -> bool {
+        let mut stack: Vec<Token> = self.children_tokens(arena).collect();
+        while let Some(token) = stack.pop() {
+            match f(&arena[token]) {
+                true => arena.uproot(token),
+                false => stack.extend(token.children_tokens(arena))
+            }
+        }
+    }
+
+    /// Returns an iterator of tokens of the subtree nodes (including the node
+    /// itself) visited in order of priority. The iterator keeps a frontier of
+    /// nodes in a binary heap and always visits the node that compares least
+    /// according to `cmp`, adding its children to the frontier. A child is
+    /// therefore never visited before its parent. Traversing the entire
+    /// subtree takes O(n log n) time.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = 0usize;
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let child1 = root_token.append(&mut arena, 5usize);
+    /// let child2 = root_token.append(&mut arena, 1usize);
+    /// child1.append(&mut arena, 2usize);
+    /// child2.append(&mut arena, 7usize);
+    /// child2.append(&mut arena, 3usize);
+    ///
+    /// let visited: Vec<_> = root_token
+    ///     .best_first(&arena, |x, y| x.data.cmp(&y.data))
+    ///     .map(|t| arena[t].data)
+    ///     .collect();
+    /// assert_eq!(&visited[..], &[0, 1, 3, 5, 2, 7]);
+    /// ```
+    pub fn best_first<'a, T, F>(self, arena: &'a Arena<T>, cmp: F)
+        -> BestFirstTokens<'a, T, F>
+        where F: FnMut(&Node<T>, &Node<T>) -> Ordering {
+        if arena.get(self).is_none() { panic!("Invalid token") }
+        let mut iter = BestFirstTokens { arena, heap: Vec::new(), cmp };
+        iter.push(self);
+        iter
+    }
+
+    /// Flattens the subtree rooted at the node into a single chain in
+    /// pre-order: every node in the subtree ends up with the node that follows
+    /// it in a pre-order traversal as its only child. The nodes are relinked in
+    /// place and the node itself keeps its position in the tree.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    /// let romance = root_token.append(&mut arena, "Romance");
+    ///
+    /// root_token.flatten(&mut arena);
+    /// assert_eq!(arena[root_token].first_child(), Some(germanic));
+    /// assert_eq!(arena[germanic].first_child(), Some(english));
+    /// assert_eq!(arena[english].first_child(), Some(romance));
+    /// assert!(arena[romance].is_leaf());
+    /// assert!(arena[germanic].next_sibling().is_none());
+    /// ```
+    pub fn flatten<T>(self, arena: &mut Arena<T>) {
+        let tokens: Vec<Token> = self.subtree_tokens(arena, TraversalOrder::Pre)
+            .collect();
+        for pair in tokens.windows(2) {
+            let (parent, child) = (pair[0], pair[1]);
+            let node = &mut arena[parent];
+            node.first_child = Some(child);
+            node.last_child = Some(child);
+            let node = &mut arena[child];
+            node.parent = Some(parent);
+            node.previous_sibling = None;
+            node.next_sibling = None;
+        }
+        if let Some(&last) = tokens.last() {
+            let node = &mut arena[last];
+            node.first_child = None;
+            node.last_child = None;
+        }
+    }
+
+    /// Returns the number of nodes in the subtree that are exactly `depth`
+    /// levels below the node. The node itself sits at depth 0. The subtree is
+    /// walked level by level and the walk stops once the given depth is
+    /// reached.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// germanic.append(&mut arena, "English");
+    /// germanic.append(&mut arena, "German");
+    /// romance.append(&mut arena, "French");
+    ///
+    /// assert_eq!(root_token.count_at_depth(&arena, 0), 1);
+    /// assert_eq!(root_token.count_at_depth(&arena, 1), 2);
+    /// assert_eq!(root_token.count_at_depth(&arena, 2), 3);
+    /// assert_eq!(root_token.count_at_depth(&arena, 3), 0);
+    /// ```
+    pub fn count_at_depth<T>(self, arena: &Arena<T>, depth: usize) -> usize {
+        if arena.get(self).is_none() { panic!("Invalid token") }
+        let mut level: Vec<Token> = vec![self];
+        for _ in 0..depth {
+            level = level.into_iter()
+                .flat_map(|token| token.children_tokens(arena))
+                .collect();
+            if level.is_empty() { break }
+        }
+        level.len()
+    }
+
+    /// Returns the number of siblings of the node, not counting the node
+    /// itself. Nodes without a parent and only children have no siblings.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// root_token.append(&mut arena, "Romance");
+    /// let english = germanic.append(&mut arena, "English");
+    ///
+    /// assert_eq!(germanic.sibling_count(&arena), 1);
+    /// assert_eq!(english.sibling_count(&arena), 0);
+    /// assert_eq!(root_token.sibling_count(&arena), 0);
+    /// ```
+    pub fn sibling_count<T>(self, arena: &Arena<T>) -> usize {
+        match arena.get(self) {
+            None => panic!("Invalid token"),
+            Some(node) => match node.parent {
+                None => 0,
+                Some(_) => self.preceding_siblings_tokens(arena).count()
+                    + self.following_siblings_tokens(arena).count()
+            }
+        }
+    }
+
+    /// Creates a new node with the data built by the given closure and appends
+    /// it to the given node. The closure is called with the token the new node
+    /// will have, which lets the data refer to its own node.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::{Arena, Token};
+    ///
+    /// let (mut arena, root_token) = Arena::with_data((None, "Indo-European"));
+    /// let germanic = root_token.append_with(&mut arena, |token| {
+    ///     (Some(token), "Germanic")
+    /// });
+    /// assert_eq!(arena[germanic].data, (Some(germanic), "Germanic"));
+    /// assert_eq!(arena[germanic].parent(), Some(root_token));
+    /// ```
+    pub fn append_with<T, F>(self, arena: &mut Arena<T>, f: F) -> Token
+        where F: FnOnce(Token) -> T {
+        if arena.get(self).is_none() { panic!("Invalid token") }
+        // the token of the next node is known ahead of the insertion
+        let data = f(arena.allocator.head());
+        let new_node_token = arena.new_node(data);
+        self.link_append(arena, new_node_token);
+        new_node_token
+    }
+
+    /// Returns the sum of the weights of the nodes in the subtree (including
+    /// the node itself), where the weight of each node is computed from its
+    /// data by the given function. With a constant weight of 1 this is the
+    /// number of nodes in the subtree.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "English");
+    ///
+    /// assert_eq!(root_token.weighted_size(&arena, |_| 1), 3);
+    /// assert_eq!(germanic.weighted_size(&arena, |x| x.len() as u64), 15);
+    /// ```
+    pub fn weighted_size<T, F>(self, arena: &Arena<T>, weight: F) -> u64
+        where F: Fn(&T) -> u64 {
+        self.fold_subtree(arena, 0, TraversalOrder::Pre,
+                          |acc, node| acc + weight(&node.data))
+    }
+
+    /// Partitions the children of the node into `min(n, number of children)`
+    /// chunks of roughly equal sizes, preserving the order of the children.
+    /// Since the subtrees of the children are disjoint, each chunk can be
+    /// handed to a separate thread. A leaf yields no chunks.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena or if
+    /// `n` is 0.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = 0usize;
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let children = arena.append_many_children(root_token, 1..8);
+    ///
+    /// let chunks = root_token.children_chunks(&arena, 3);
+    /// assert_eq!(chunks.len(), 3);
+    /// assert_eq!(&chunks[0][..], &children[0..3]);
+    /// assert_eq!(&chunks[1][..], &children[3..5]);
+    /// assert_eq!(&chunks[2][..], &children[5..7]);
+    /// ```
+    pub fn children_chunks<T>(self, arena: &Arena<T>, n: usize)
+        -> Vec<Vec<Token>> {
+        if n == 0 { panic!("Number of chunks must be non-zero") }
+        let children: Vec<Token> = self.children_tokens(arena).collect();
+        let n = n.min(children.len());
+        let mut chunks = Vec::with_capacity(n);
+        let mut rest = &children[..];
+        for i in 0..n {
+            // the first few chunks take the remainder one at a time
+            let size = children.len() / n + (i < children.len() % n) as usize;
+            let (chunk, remainder) = rest.split_at(size);
+            chunks.push(chunk.to_vec());
+            rest = remainder;
+        }
+        chunks
+    }
+
+    /// Returns an iterator of clones of the data of the subtree nodes in the
+    /// given order. The items are owned and can outlive the borrow of the
+    /// arena.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    /// use atree::iter::TraversalOrder;
+    ///
+    /// let root_data = String::from("Indo-European");
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// root_token.append(&mut arena, String::from("Germanic"));
+    ///
+    /// let data: Vec<String> = root_token
+    ///     .subtree_cloned(&arena, TraversalOrder::Post)
+    ///     .collect();
+    /// drop(arena);
+    /// assert_eq!(&data[..], &["Germanic", "Indo-European"]);
+    /// ```
+    pub fn subtree_cloned<'a, T: Clone>(self, arena: &'a Arena<T>,
+                                        order: TraversalOrder)
+        -> impl Iterator<Item = T> + 'a {
+        self.subtree(arena, order).map(|node| node.data.clone())
+    }
+
+    /// Returns an iterator of tokens of the node itself followed by its
+    /// ancestors, up to the root.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    ///
+    /// let tokens: Vec<_> = english.ancestors_inclusive_tokens(&arena).collect();
+    /// assert_eq!(&tokens[..], &[english, germanic, root_token]);
+    /// ```
+    pub fn ancestors_inclusive_tokens<'a, T>(self, arena: &'a Arena<T>)
+        -> AncestorTokens<'a, T> {
+        if arena.get(self).is_none() { panic!("Invalid token") }
+        AncestorTokens { arena, node_token: Some(self) }
+    }
+
+    /// Returns an iterator of references of the node itself followed by its
+    /// ancestors, up to the root.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    ///
+    /// let data: Vec<_> = english.ancestors_inclusive(&arena)
+    ///     .map(|x| x.data)
+    ///     .collect();
+    /// assert_eq!(&data[..], &["English", "Germanic", "Indo-European"]);
+    /// ```
+    pub fn ancestors_inclusive<'a, T>(self, arena: &'a Arena<T>)
+        -> Ancestors<'a, T> {
+        Ancestors { token_iter: self.ancestors_inclusive_tokens(arena) }
+    }
+
+    /// Rotates the node up the tree: the node takes the place of its parent
+    /// among the children of its grandparent (or becomes a root if the parent
+    /// was one) and the former parent, along with its other descendants,
+    /// becomes the last child of the node.
+    ///
+    /// # Errors:
+    ///
+    /// Returns `Err(Error::NoParent)` if the node is a root.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::{Arena, Error};
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let west = germanic.append(&mut arena, "West");
+    /// let north = germanic.append(&mut arena, "North");
+    /// let english = west.append(&mut arena, "English");
+    ///
+    /// west.rotate_up(&mut arena).unwrap();
+    /// assert_eq!(arena[west].parent(), Some(root_token));
+    /// assert_eq!(arena[germanic].parent(), Some(west));
+    /// let children: Vec<_> = west.children_tokens(&arena).collect();
+    /// assert_eq!(&children[..], &[english, germanic]);
+    /// let children: Vec<_> = germanic.children_tokens(&arena).collect();
+    /// assert_eq!(&children[..], &[north]);
+    ///
+    /// assert_eq!(root_token.rotate_up(&mut arena), Err(Error::NoParent));
+    /// ```
+    pub fn rotate_up<T>(self, arena: &mut Arena<T>) -> Result<(), Error> {
+        let parent = match arena.get(self) {
+            None => panic!("Invalid token"),
+            Some(node) => match node.parent {
+                None => return Err(Error::NoParent),
+                Some(parent) => parent
+            }
+        };
+        self.detach(arena);
+        // self is free-standing after the detach so this cannot fail
+        parent.replace_node(arena, self)?;
+        self.link_append(arena, parent);
+        Ok(())
+    }
+
+    /// Returns the node that follows the current node in document order, i.e.
+    /// its successor in a pre-order traversal of the whole tree: the first
+    /// child of the node if it has one, or else the next sibling of the node or
+    /// of its closest ancestor that has one. Returns `None` at the end of the
+    /// tree.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    /// let romance = root_token.append(&mut arena, "Romance");
+    ///
+    /// assert_eq!(root_token.next_in_document_order(&arena), Some(germanic));
+    /// assert_eq!(germanic.next_in_document_order(&arena), Some(english));
+    /// assert_eq!(english.next_in_document_order(&arena), Some(romance));
+    /// assert!(romance.next_in_document_order(&arena).is_none());
+    /// ```
+    pub fn next_in_document_order<T>(self, arena: &Arena<T>) -> Option<Token> {
+        match arena.get(self) {
+            None => panic!("Invalid token"),
+            Some(node) => match node.first_child {
+                Some(child) => Some(child),
+                None => self.ancestors_inclusive(arena)
+                    .find_map(|node| node.next_sibling)
+            }
+        }
+    }
+
+    /// Returns the node that precedes the current node in document order, i.e.
+    /// its predecessor in a pre-order traversal of the whole tree: the last
+    /// descendant of the previous sibling of the node if it has one, or else
+    /// the parent of the node. Returns `None` at the start of the tree.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    /// let romance = root_token.append(&mut arena, "Romance");
+    ///
+    /// assert_eq!(romance.previous_in_document_order(&arena), Some(english));
+    /// assert_eq!(english.previous_in_document_order(&arena), Some(germanic));
+    /// assert_eq!(germanic.previous_in_document_order(&arena), Some(root_token));
+    /// assert!(root_token.previous_in_document_order(&arena).is_none());
+    /// ```
+    pub fn previous_in_document_order<T>(self, arena: &Arena<T>)
+        -> Option<Token> {
+        match arena.get(self) {
+            None => panic!("Invalid token"),
+            Some(node) => match node.previous_sibling {
+                None => node.parent,
+                Some(mut token) => {
+                    while let Some(child) = arena[token].last_child {
+                        token = child;
+                    }
+                    Some(token)
+                }
+            }
+        }
+    }
+
+    /// Returns the first leaf that follows the current node in document order
+    /// (see [`next_in_document_order`]), or `None` if there is no leaf after
+    /// the node in its tree.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// let french = romance.append(&mut arena, "French");
+    ///
+    /// assert_eq!(root_token.next_leaf(&arena), Some(english));
+    /// assert_eq!(english.next_leaf(&arena), Some(french));
+    /// assert!(french.next_leaf(&arena).is_none());
+    /// ```
+    ///
+    /// [`next_in_document_order`]: struct.Token.html#method.next_in_document_order
+    pub fn next_leaf<T>(self, arena: &Arena<T>) -> Option<Token> {
+        let mut token = self.next_in_document_order(arena)?;
+        while let Some(child) = arena[token].first_child {
+            token = child;
+        }
+        Some(token)
+    }
+
+    /// Returns the internal (non-leaf) nodes of the subtree in pre-order, each
+    /// paired with the tokens of its children in the order of insertion. Leaves
+    /// are omitted. This is the adjacency list of the subtree.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// let english = germanic.append(&mut arena, "English");
+    ///
+    /// let groups = root_token.subtree_grouped_by_parent(&arena);
+    /// assert_eq!(groups, vec![(root_token, vec![germanic, romance]),
+    ///                         (germanic, vec![english])]);
+    /// ```
+    pub fn subtree_grouped_by_parent<T>(self, arena: &Arena<T>)
+        -> Vec<(Token, Vec<Token>)> {
+        self.subtree(arena, TraversalOrder::Pre)
+            .filter(|node| !node.is_leaf())
+            .map(|node| (node.token, node.children_tokens(arena).collect()))
+            .collect()
+    }
+
+    /// Mirrors the subtree rooted at the node by reversing the order of the
+    /// children of every node in it. Mirroring twice restores the original
+    /// subtree.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    /// use atree::iter::TraversalOrder;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "English");
+    /// germanic.append(&mut arena, "German");
+    /// root_token.append(&mut arena, "Romance");
+    ///
+    /// root_token.mirror(&mut arena);
+    /// let subtree: Vec<_> = root_token.subtree(&arena, TraversalOrder::Pre)
+    ///     .map(|x| x.data)
+    ///     .collect();
+    /// assert_eq!(&subtree[..], &["Indo-European", "Romance", "Germanic",
+    ///                            "German", "English"]);
+    /// ```
+    pub fn mirror<T>(self, arena: &mut Arena<T>) {
+        let tokens: Vec<Token> = self.subtree_tokens(arena, TraversalOrder::Pre)
+            .collect();
+        for token in tokens {
+            let children: Vec<Token> = token.children_tokens(arena).collect();
+            for &child in &children {
+                let node = &mut arena[child];
+                mem::swap(&mut node.previous_sibling, &mut node.next_sibling);
+            }
+            let node = &mut arena[token];
+            mem::swap(&mut node.first_child, &mut node.last_child);
+        }
+    }
+
+    /// Returns the diameter of the subtree rooted at the node, i.e. the number
+    /// of edges on the longest path between any two nodes of the subtree. The
+    /// path does not have to go through the node itself.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let west = germanic.append(&mut arena, "West");
+    /// root_token.append(&mut arena, "Romance");
+    /// west.append(&mut arena, "English");
+    ///
+    /// // English -> West -> Germanic -> Indo-European -> Romance
+    /// assert_eq!(root_token.diameter(&arena), 4);
+    /// assert_eq!(west.diameter(&arena), 1);
+    /// ```
+    pub fn diameter<T>(self, arena: &Arena<T>) -> usize {
+        // computes the height of every node along with the diameter of its
+        // subtree, from the two tallest children of the node
+        let (_, diameter) = self.reduce_bottom_up(arena, |_, children| {
+            let (mut first, mut second) = (0, 0);
+            let mut diameter = 0;
+            for &(height, child_diameter) in children {
+                diameter = diameter.max(child_diameter);
+                match height + 1 {
+                    h if h > first => { second = first; first = h; },
+                    h if h > second => second = h,
+                    _ => ()
+                }
+            }
+            (first, diameter.max(first + second))
+        });
+        diameter
+    }
+
+    /// Returns the sibling `offset` positions away from the node: following
+    /// siblings for positive offsets, preceding siblings for negative ones and
+    /// the node itself for an offset of zero. Returns `None` if there is no
+    /// sibling that far away.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let slavic = root_token.append(&mut arena, "Slavic");
+    ///
+    /// assert_eq!(germanic.sibling_at_offset(&arena, 1), Some(slavic));
+    /// assert_eq!(slavic.sibling_at_offset(&arena, -2), Some(romance));
+    /// assert_eq!(romance.sibling_at_offset(&arena, 0), Some(romance));
+    /// assert!(romance.sibling_at_offset(&arena, -1).is_none());
+    /// ```
+    pub fn sibling_at_offset<T>(self, arena: &Arena<T>, offset: isize)
+        -> Option<Token> {
+        let mut token = self;
+        for _ in 0..offset.unsigned_abs() {
+            let node = match arena.get(token) {
+                Some(n) => n,
+                None => panic!("Invalid token")
+            };
+            token = match offset > 0 {
+                true => node.next_sibling?,
+                false => node.previous_sibling?
+            };
+        }
+        match arena.get(token) {
+            Some(_) => Some(token),
+            None => panic!("Invalid token")
+        }
+    }
+
+    /// Returns an iterator of `(parent, child)` token pairs, one for every
+    /// parent-child relationship in the subtree of the given node, in pre-order
+    /// of the children.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    /// let romance = root_token.append(&mut arena, "Romance");
+    ///
+    /// let edges: Vec<_> = root_token.edges(&arena).collect();
+    /// assert_eq!(&edges[..], &[(root_token, germanic),
+    ///                          (germanic, english),
+    ///                          (root_token, romance)]);
+    /// ```
+    pub fn edges<'a, T>(self, arena: &'a Arena<T>)
+        -> impl Iterator<Item = (Token, Token)> + 'a {
+        self.subtree(arena, TraversalOrder::Pre)
+            .filter(move |node| node.token != self)
+            .map(|node| match node.parent {
+                Some(parent) => (parent, node.token),
+                None => panic!("Corrupt arena")
+            })
+    }
+
+    /// Checks whether `other` lies within the subtree of the given node, that
+    /// is, whether it is the node itself or one of its descendants. Only the
+    /// ancestors of `other` are visited.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the tokens do not correspond to nodes in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// let english = germanic.append(&mut arena, "English");
+    ///
+    /// assert!(germanic.subtree_contains(&arena, english));
+    /// assert!(germanic.subtree_contains(&arena, germanic));
+    /// assert!(!germanic.subtree_contains(&arena, romance));
+    /// ```
+    pub fn subtree_contains<T>(self, arena: &Arena<T>, other: Token) -> bool {
+        if arena.get(self).is_none() { panic!("Invalid token") }
+        other.ancestors_inclusive_tokens(arena).any(|token| token == self)
+    }
+
+    /// Returns the size of the subtree rooted at every node in the subtree of
+    /// the given node (each node counting itself), computed in a single
+    /// post-order pass.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    /// germanic.append(&mut arena, "Dutch");
+    ///
+    /// let sizes = root_token.subtree_sizes(&arena);
+    /// assert_eq!(sizes[&root_token], 4);
+    /// assert_eq!(sizes[&germanic], 3);
+    /// assert_eq!(sizes[&english], 1);
+    /// ```
+    pub fn subtree_sizes<T>(self, arena: &Arena<T>) -> HashMap<Token, usize> {
+        let mut sizes = HashMap::new();
+        for token in self.subtree_tokens(arena, TraversalOrder::Post) {
+            // children are visited before their parents in post-order
+            let size = 1 + token.children_tokens(arena)
+                .map(|child| sizes[&child])
+                .sum::<usize>();
+            sizes.insert(token, size);
+        }
+        sizes
+    }
+
+    /// Removes the last child of the given node along with its descendants and
+    /// returns the data of the child together with its (now invalid) token.
+    /// Returns `None` if the node has no children.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// root_token.append(&mut arena, "Romance");
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "English");
+    ///
+    /// assert_eq!(root_token.pop_child(&mut arena), Some(("Germanic", germanic)));
+    /// assert_eq!(arena.node_count(), 2);
+    /// assert!(arena.get(germanic).is_none());
+    /// ```
+    pub fn pop_child<T>(self, arena: &mut Arena<T>) -> Option<(T, Token)> {
+        let child = self.last_child(arena)?;
+        child.remove_descendants(arena);
+        child.detach(arena);
+        match arena.free(child) {
+            Some(node) => Some((node.data, child)),
+            None => panic!("Corrupt arena")
+        }
+    }
+
+    /// Returns the path from the root of the tree down to the lowest common
+    /// ancestor of the given nodes, both ends included. Returns an empty vector
+    /// if the nodes are in different trees.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the tokens do not correspond to nodes in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    /// let dutch = germanic.append(&mut arena, "Dutch");
+    ///
+    /// assert_eq!(english.common_path(dutch, &arena), [root_token, germanic]);
+    /// assert_eq!(english.common_path(germanic, &arena), [root_token, germanic]);
+    /// ```
+    pub fn common_path<T>(self, other: Token, arena: &Arena<T>) -> Vec<Token> {
+        let mut path: Vec<Token> = self.ancestors_inclusive_tokens(arena)
+            .collect();
+        let mut other_path: Vec<Token> = other.ancestors_inclusive_tokens(arena)
+            .collect();
+        path.reverse();
+        other_path.reverse();
+        let common = path.iter()
+            .zip(other_path.iter())
+            .take_while(|(x, y)| x == y)
+            .count();
+        path.truncate(common);
+        path
+    }
+
+    /// Returns how to get from the given node to `to`, in the manner of a
+    /// relative file path: the number of steps up to their lowest common
+    /// ancestor, followed by the (zero-based) positions of the children to
+    /// step into on the way down. Returns `None` if the nodes are in different
+    /// trees.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the tokens do not correspond to nodes in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "English");
+    /// let dutch = germanic.append(&mut arena, "Dutch");
+    ///
+    /// // ../1/1
+    /// assert_eq!(romance.relative_path(dutch, &arena), Some((1, vec![1, 1])));
+    /// assert_eq!(dutch.relative_path(root_token, &arena), Some((2, vec![])));
+    /// ```
+    pub fn relative_path<T>(self, to: Token, arena: &Arena<T>)
+        -> Option<(usize, Vec<usize>)> {
+        let common = self.common_path(to, arena).len();
+        if common == 0 { return None }
+        let up = self.ancestors_inclusive_tokens(arena).count() - common;
+        let mut down: Vec<usize> = to.ancestors_inclusive_tokens(arena)
+            .map(|token| token.preceding_siblings_tokens(arena).count())
+            .collect();
+        down.truncate(down.len() - common);
+        down.reverse();
+        Some((up, down))
+    }
+
+    /// Checks whether the given node is the only child of its parent. Root
+    /// nodes are not considered only children.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    /// root_token.append(&mut arena, "Romance");
+    ///
+    /// assert!(english.is_only_child(&arena));
+    /// assert!(!germanic.is_only_child(&arena));
+    /// assert!(!root_token.is_only_child(&arena));
+    /// ```
+    pub fn is_only_child<T>(self, arena: &Arena<T>) -> bool {
+        match arena.get(self) {
+            Some(node) => node.parent.is_some()
+                && node.previous_sibling.is_none()
+                && node.next_sibling.is_none(),
+            None => panic!("Invalid token")
+        }
+    }
+
+    /// Returns the number of internal (non-leaf) nodes in the subtree of the
+    /// given node, the node itself included.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "English");
+    /// root_token.append(&mut arena, "Romance");
+    ///
+    /// assert_eq!(root_token.internal_count(&arena), 2);
+    /// assert_eq!(germanic.internal_count(&arena), 1);
+    /// ```
+    pub fn internal_count<T>(self, arena: &Arena<T>) -> usize {
+        self.subtree(arena, TraversalOrder::Pre)
+            .filter(|node| node.first_child.is_some())
+            .count()
+    }
+
+    /// Returns the sum of the depths of all the nodes in the subtree of the
+    /// given node, with depths counted from the node itself (i.e. the internal
+    /// path length of the subtree).
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "English");
+    /// root_token.append(&mut arena, "Romance");
+    ///
+    /// assert_eq!(root_token.total_depth(&arena), 4);
+    /// assert_eq!(germanic.total_depth(&arena), 1);
+    /// ```
+    pub fn total_depth<T>(self, arena: &Arena<T>) -> usize {
+        if arena.get(self).is_none() { panic!("Invalid token") }
+        let mut total = 0;
+        let mut level = vec![self];
+        let mut depth = 0;
+        while !level.is_empty() {
+            total += depth * level.len();
+            level = level.into_iter()
+                .flat_map(|token| token.children_tokens(arena))
+                .collect();
+            depth += 1;
+        }
+        total
+    }
+
+    /// Returns an iterator of tokens of ancestor nodes of the given node, from
+    /// the parent upwards, stopping right before `stop`. If `stop` is not an
+    /// ancestor of the node, the iterator carries on all the way to the root.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let west = germanic.append(&mut arena, "West");
+    /// let english = west.append(&mut arena, "English");
+    ///
+    /// let ancestors: Vec<_> = english.ancestors_until(root_token, &arena)
+    ///     .collect();
+    /// assert_eq!(&ancestors[..], &[west, germanic]);
+    /// ```
+    pub fn ancestors_until<'a, T>(self, stop: Token, arena: &'a Arena<T>)
+        -> impl Iterator<Item = Token> + 'a {
+        self.ancestors_tokens(arena).take_while(move |&token| token != stop)
+    }
+
+    /// Checks whether the given node is a proper ancestor of `other`. A node is
+    /// not its own ancestor.
+    ///
+    /// This can be used to guard operations such as [`append_node`] that do not
+    /// check for cycles themselves.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the tokens do not correspond to nodes in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    ///
+    /// assert!(root_token.is_ancestor_of(english, &arena));
+    /// assert!(!english.is_ancestor_of(germanic, &arena));
+    /// assert!(!germanic.is_ancestor_of(germanic, &arena));
+    /// ```
+    ///
+    /// [`append_node`]: struct.Token.html#method.append_node
+    pub fn is_ancestor_of<T>(self, other: Token, arena: &Arena<T>) -> bool {
+        if arena.get(self).is_none() { panic!("Invalid token") }
+        other.ancestors_tokens(arena).any(|token| token == self)
+    }
+
+    /// Checks whether the given node is a proper descendant of `other`. A node
+    /// is not its own descendant.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the tokens do not correspond to nodes in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    ///
+    /// assert!(english.is_descendant_of(root_token, &arena));
+    /// assert!(!germanic.is_descendant_of(english, &arena));
+    /// assert!(!germanic.is_descendant_of(germanic, &arena));
+    /// ```
+    pub fn is_descendant_of<T>(self, other: Token, arena: &Arena<T>) -> bool {
+        other.is_ancestor_of(self, arena)
+    }
+
+    /// Returns an iterator of cloned data of the subtree nodes of the given
+    /// node in pre-order, each paired with its depth relative to the node. The
+    /// iterator owns its items and does not hold on to the arena.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "English");
+    /// root_token.append(&mut arena, "Romance");
+    ///
+    /// let outline = root_token.outline(&arena);
+    /// arena.new_node("Uralic");  // the arena is free to change
+    /// let outline: Vec<_> = outline.collect();
+    /// assert_eq!(&outline[..], &[(0, "Indo-European"), (1, "Germanic"),
+    ///                            (2, "English"), (1, "Romance")]);
+    /// ```
+    pub fn outline<T: Clone>(self, arena: &Arena<T>)
+        -> impl Iterator<Item = (usize, T)> {
+        let outline: Vec<(usize, T)> = self
+            .subtree_with_depth(arena, TraversalOrder::Pre)
+            .map(|(depth, node)| (depth, node.data.clone()))
+            .collect();
+        outline.into_iter()
+    }
+
+    /// Checks whether the heights of the subtrees of the children of every node
+    /// in the subtree of the given node differ by at most `tolerance`. With a
+    /// tolerance of 1, this is the balance condition of AVL trees extended to
+    /// any number of children (leaves have a height of 0).
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let west = germanic.append(&mut arena, "West");
+    /// root_token.append(&mut arena, "Romance");
+    /// assert!(root_token.is_balanced(&arena, 1));
+    ///
+    /// west.append(&mut arena, "English");
+    /// assert!(!root_token.is_balanced(&arena, 1));
+    /// assert!(root_token.is_balanced(&arena, 2));
+    /// ```
+    pub fn is_balanced<T>(self, arena: &Arena<T>, tolerance: usize) -> bool {
+        // computes the height of every node along with whether its subtree is
+        // balanced
+        let (_, balanced): (usize, _) = self.reduce_bottom_up(arena, |_, children| {
+            let heights = children.iter().map(|&(height, _)| height);
+            match (heights.clone().min(), heights.max()) {
+                (Some(min), Some(max)) => {
+                    let balanced = max - min <= tolerance
+                        && children.iter().all(|&(_, balanced)| balanced);
+                    (max + 1, balanced)
+                },
+                _ => (0, true)
+            }
+        });
+        balanced
+    }
+
+    /// Returns an iterator of references of subtree nodes of the given node,
+    /// each paired with its depth relative to the node (which has a depth of
+    /// 0).
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    /// use atree::iter::TraversalOrder;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "English");
+    /// root_token.append(&mut arena, "Romance");
+    ///
+    /// let pairs: Vec<_> = root_token
+    ///     .subtree_with_depth(&arena, TraversalOrder::Post)
+    ///     .map(|(depth, node)| (depth, node.data))
+    ///     .collect();
+    /// assert_eq!(&pairs[..], &[(2, "English"), (1, "Germanic"), (1, "Romance"),
+    ///                          (0, "Indo-European")]);
+    /// ```
+    pub fn subtree_with_depth<'a, T>(self, arena: &'a Arena<T>,
+                                     order: TraversalOrder)
+        -> impl Iterator<Item = (usize, &'a Node<T>)> + 'a {
+        SubtreeWithDepth {
+            iter: self.subtree_tokens(arena, order),
+            order,
+            previous: None
+        }
+    }
+
+    /// Returns the position of the node in a pre-order traversal of its whole
+    /// tree, starting from 0 at the root.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "English");
+    /// let romance = root_token.append(&mut arena, "Romance");
+    ///
+    /// assert_eq!(root_token.document_position(&arena), 0);
+    /// assert_eq!(germanic.document_position(&arena), 1);
+    /// assert_eq!(romance.document_position(&arena), 3);
+    /// ```
+    pub fn document_position<T>(self, arena: &Arena<T>) -> usize {
+        // every node on the way down from the root is preceded by its parent
+        // and by the subtrees of its preceding siblings
+        self.ancestors_inclusive_tokens(arena)
+            .filter(|&token| arena[token].parent.is_some())
+            .map(|token| 1 + token.preceding_siblings_tokens(arena)
+                .map(|sibling| sibling.subtree_tokens(arena, TraversalOrder::Pre)
+                    .count())
+                .sum::<usize>())
+            .sum()
+    }
+
+    /// Returns an iterator of references of child nodes from the last child to
+    /// the first.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// root_token.append(&mut arena, "Romance");
+    /// root_token.append(&mut arena, "Germanic");
+    /// root_token.append(&mut arena, "Slavic");
+    ///
+    /// let children: Vec<_> = root_token.children_rev(&arena)
+    ///     .map(|x| x.data)
+    ///     .collect();
+    /// assert_eq!(&children[..], &["Slavic", "Germanic", "Romance"]);
+    /// ```
+    pub fn children_rev<'a, T>(self, arena: &'a Arena<T>)
+        -> impl Iterator<Item = &'a Node<T>> + 'a {
+        let last_child = match arena.get(self) {
+            Some(n) => n.last_child,
+            None => panic!("Invalid token")
+        };
+        ChildrenRevTokens { arena, node_token: last_child }
+            .map(move |token| &arena[token])
+    }
+
+    /// Returns the tokens of the nodes in the subtree of the given node (in
+    /// pre-order) whose subtree sizes satisfy the given predicate. Each node
+    /// counts itself in its subtree size.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "English");
+    /// root_token.append(&mut arena, "Romance");
+    ///
+    /// let branches = root_token.subtrees_where_size(&arena, |size| size >= 2);
+    /// assert_eq!(&branches[..], &[root_token, germanic]);
+    /// ```
+    pub fn subtrees_where_size<T, F>(self, arena: &Arena<T>, f: F) -> Vec<Token>
+        where F: Fn(usize) -> bool {
+        let sizes = self.subtree_sizes(arena);
+        self.subtree_tokens(arena, TraversalOrder::Pre)
+            .filter(|token| f(sizes[token]))
+            .collect()
+    }
+
+    /// Moves every node in the subtree of the given node that satisfies the
+    /// predicate, along with its descendants, into an arena of its own, and
+    /// returns the new arenas with the tokens of their roots in pre-order. The
+    /// descendants of a matching node are not tested themselves. The node
+    /// itself is extracted if it matches.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// germanic.append(&mut arena, "English");
+    /// root_token.append(&mut arena, "Romance");
+    ///
+    /// let extracted = root_token.extract_matching(&mut arena, |x| x.data == "Germanic");
+    /// assert_eq!(extracted.len(), 1);
+    /// let (germanic_arena, germanic_root) = &extracted[0];
+    /// assert_eq!(germanic_arena[*germanic_root].data, "Germanic");
+    /// assert_eq!(germanic_arena.node_count(), 2);
+    /// assert_eq!(arena.node_count(), 2);
+    /// ```
+    pub fn extract_matching<T: Clone, F>(self, arena: &mut Arena<T>, mut f: F)
+        -> Vec<(Arena<T>, Token)>
+        where F: FnMut(&Node<T>) -> bool {
+        let mut matches = Vec::new();
+        let mut stack = vec![self];
+        while let Some(token) = stack.pop() {
+            match f(&arena[token]) {
+                true => matches.push(token),
+                // push in reverse so that the children are visited in order
+                false => stack.extend(token.children_rev(arena)
+                    .map(|node| node.token))
+            }
+        }
+        matches.into_iter()
+            .map(|token| arena.split_at(token))
+            .collect()
+    }
+
+    /// Returns an iterator of tokens of all the siblings of the given node in
+    /// the order of insertion, the node itself included. A node without a
+    /// parent is its own only sibling.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let slavic = root_token.append(&mut arena, "Slavic");
+    ///
+    /// let siblings: Vec<_> = germanic.siblings_tokens(&arena).collect();
+    /// assert_eq!(&siblings[..], &[romance, germanic, slavic]);
+    /// ```
+    pub fn siblings_tokens<'a, T>(self, arena: &'a Arena<T>)
+        -> impl Iterator<Item = Token> + 'a {
+        let parent = match arena.get(self) {
+            Some(n) => n.parent,
+            None => panic!("Invalid token")
+        };
+        let first_sibling = match parent {
+            Some(parent) => arena[parent].first_child,
+            None => Some(self)
+        };
+        // a node without a parent is its own only sibling even if it has been
+        // linked to other nodes through insert_before/insert_after
+        std::iter::successors(first_sibling, move |&token| match parent {
+            Some(_) => arena[token].next_sibling,
+            None => None
+        })
+    }
+
+    /// Returns an iterator of tokens of all the siblings of the given node in
+    /// the order of insertion, leaving out the node itself.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let romance = root_token.append(&mut arena, "Romance");
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let slavic = root_token.append(&mut arena, "Slavic");
+    ///
+    /// let siblings: Vec<_> = germanic.siblings_tokens_excluding_self(&arena)
+    ///     .collect();
+    /// assert_eq!(&siblings[..], &[romance, slavic]);
+    /// ```
+    pub fn siblings_tokens_excluding_self<'a, T>(self, arena: &'a Arena<T>)
+        -> impl Iterator<Item = Token> + 'a {
+        self.siblings_tokens(arena).filter(move |&token| token != self)
+    }
+
+    /// Returns the number of children of the given node.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// root_token.append(&mut arena, "Romance");
+    ///
+    /// assert_eq!(root_token.children_count(&arena), 2);
+    /// assert_eq!(germanic.children_count(&arena), 0);
+    /// ```
+    pub fn children_count<T>(self, arena: &Arena<T>) -> usize {
+        let mut child = match arena.get(self) {
+            Some(n) => n.first_child,
+            None => panic!("Invalid token")
+        };
+        let mut count = 0;
+        while let Some(token) = child {
+            count += 1;
+            child = arena[token].next_sibling;
+        }
+        count
+    }
+
+    /// Returns the token of the `n`th child (counting from 0) of the given node
+    /// in the order of insertion, or `None` if the node has no more than `n`
+    /// children.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// root_token.append(&mut arena, "Romance");
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    ///
+    /// assert_eq!(root_token.nth_child(&arena, 1), Some(germanic));
+    /// assert!(root_token.nth_child(&arena, 2).is_none());
+    /// ```
+    pub fn nth_child<T>(self, arena: &Arena<T>, n: usize) -> Option<Token> {
+        let mut child = match arena.get(self) {
+            Some(node) => node.first_child,
+            None => panic!("Invalid token")
+        };
+        for _ in 0..n {
+            child = arena[child?].next_sibling;
+        }
+        child
+    }
+
+    /// Returns an iterator of the paths from the given node down to each leaf
+    /// of its subtree, both ends included, with the leaves taken in pre-order.
+    /// Paths are built one at a time as the iterator advances.
+    ///
+    /// # Panics:
+    ///
+    /// Panics if the token does not correspond to a node in the arena.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use atree::Arena;
+    ///
+    /// let root_data = "Indo-European";
+    /// let (mut arena, root_token) = Arena::with_data(root_data);
+    /// let germanic = root_token.append(&mut arena, "Germanic");
+    /// let english = germanic.append(&mut arena, "English");
+    /// let romance = root_token.append(&mut arena, "Romance");
+    ///
+    /// let paths: Vec<_> = root_token.leaf_paths_iter(&arena).collect();
+    /// assert_eq!(paths, vec![vec![root_token, germanic, english],
+    ///                        vec![root_token, romance]]);
+    /// ```
+    pub fn leaf_paths_iter<'a, T>(self, arena: &'a Arena<T>)
+        -> impl Iterator<Item = Vec<Token>> + 'a {
+        self.subtree(arena, TraversalOrder::Pre)
+            .filter(|node| node.is_leaf())
+            .map(move |leaf| {
+                let mut path: Vec<Token> = leaf.token
+                    .ancestors_inclusive_tokens(arena)
+                    .take_while(|&token| token != self)
+                    .collect();
+                path.push(self);
+                path.reverse();
+                path
+            })
+    }
+
+    /// Links a free-standing node (one without parent or siblings) as the last
+    /// child of the current node.
+    pub (crate) fn link_append<T>(self, arena: &mut Arena<T>, other: Token) {
+        let previous_sibling = self.last_child(arena);
+        match previous_sibling {
+            // last_child will have checked indexability so this will not
+            // fail
+            None => arena[self].first_child = Some(other),
+            Some(sibling) => match arena.get_mut(sibling) {
+                None => panic!("Corrupt arena"),
+                Some(node) => node.next_sibling = Some(other)
+            }
+        }
+        arena[self].last_child = Some(other);
+
+        match arena.get_mut(other) {
+            None => panic!("Invalid token"),
+            Some(node) => {
+                node.parent = Some(self);
+                node.previous_sibling = previous_sibling;
+            }
+        }
+    }
+
+    /// Links a free-standing node (one without parent or siblings) as the
+    /// previous sibling of the current node.
+    pub (crate) fn link_before<T>(self, arena: &mut Arena<T>, other: Token) {
+        let (parent, previous_sibling) = match arena.get_mut(self) {
+            None => panic!("Invalid token"),
+            Some(node) => {
+                let previous_sibling = node.previous_sibling;
+                node.previous_sibling = Some(other);
+                (node.parent, previous_sibling)
+            }
+        };
+
+        match previous_sibling {
+            Some(sibling) => match arena.get_mut(sibling) {
+                None => panic!("Corrupt arena"),
+                Some(node) => node.next_sibling = Some(other)
+            },
+            None => match parent {
+                None => panic!("Cannot insert as the previous sibling of the \
+                                root node"),
+                Some(p) => match arena.get_mut(p) {
+                    None => panic!("Corrupt arena"),
+                    Some(node) => node.first_child = Some(other)
+                }
+            }
+        }
+
+        match arena.get_mut(other) {
+            None => panic!("Invalid token"),
+            Some(node) => {
+                node.parent = parent;
+                node.previous_sibling = previous_sibling;
+                node.next_sibling = Some(self);
+            }
+        }
+    }
+
+    /// Links a free-standing node (one without parent or siblings) as the next
+    /// sibling of the current node.
+    pub (crate) fn link_after<T>(self, arena: &mut Arena<T>, other: Token) {
+        let (parent, next_sibling) = match arena.get_mut(self) {
+            None => panic!("Invalid token"),
+            Some(node) => {
+                let next_sibling = node.next_sibling;
+                node.next_sibling = Some(other);
+                (node.parent, next_sibling)
+            }
+        };
+
+        match next_sibling {
+            Some(sibling) => match arena.get_mut(sibling) {
+                None => panic!("Corrupt arena"),
+                Some(node) => node.previous_sibling = Some(other)
+            },
+            None => if let Some(p) = parent {
+                match arena.get_mut(p) {
+                    None => panic!("Corrupt arena"),
+                    Some(node) => node.last_child = Some(other)
+                }
+            }
+        }
+
+        match arena.get_mut(other) {
+            None => panic!("Invalid token"),
+            Some(node) => {
+                node.parent = parent;
+                node.previous_sibling = Some(self);
+                node.next_sibling = next_sibling;
+            }
+        }
+    }
+
+    /// Removes all descendants of the current node.
+    pub (crate) fn remove_descendants<T>(self, arena: &mut Arena<T>) {
+        // This will not silently fail since postorder_next will panic if self
+        // isn't valid.  This won't do anything if self has no descendants, but
         // that's the intended behavior.
         if let (Some(mut token), mut branch) =
             postorder_next(self, self, Branch::Child, arena) {
             while branch != Branch::None {
                 let (t, b) = postorder_next(token, self, branch, arena);
-                arena.allocator.remove(token);  // should not fail (not here anyway)
+                arena.free(token);  // should not fail (not here anyway)
                 token = t.unwrap();
                 branch = b;
             }
-            arena[self].first_child = None;
+            let node = &mut arena[self];
+            node.first_child = None;
+            node.last_child = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::cognitive_complexity)]
+    fn replace_node() {
+        // root node that we will attach subtrees to
+        let root_data = "Indo-European";
+        let (mut arena, root) = Arena::with_data(root_data);
+       
+        // the Germanic branch
+        let germanic = root.append(&mut arena, "Germanic");
+        let west = germanic.append(&mut arena, "West");
+        west.append(&mut arena, "Scots");
+        west.append(&mut arena, "English");
+       
+        // the slavic branch
+        let slavic = root.append(&mut arena, "Slavic");
+        slavic.append(&mut arena, "Polish");
+        slavic.append(&mut arena, "Russian");
+       
+        let mut iter = root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data);
+        assert_eq!(iter.next(), Some("Indo-European"));
+        assert_eq!(iter.next(), Some("Germanic"));
+        assert_eq!(iter.next(), Some("West"));
+        assert_eq!(iter.next(), Some("Scots"));
+        assert_eq!(iter.next(), Some("English"));
+        assert_eq!(iter.next(), Some("Slavic"));
+        assert_eq!(iter.next(), Some("Polish"));
+        assert_eq!(iter.next(), Some("Russian"));
+        assert!(iter.next().is_none());
+
+        // the Romance branch
+        let romance = arena.new_node("Romance");
+        romance.append(&mut arena, "French");
+        romance.append(&mut arena, "Italian");
+       
+        // replace_node germanic with romance
+        germanic.replace_node(&mut arena, romance).unwrap();
+       
+        let mut iter = root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data);
+        assert_eq!(iter.next(), Some("Indo-European"));
+        assert_eq!(iter.next(), Some("Romance"));
+        assert_eq!(iter.next(), Some("French"));
+        assert_eq!(iter.next(), Some("Italian"));
+        assert_eq!(iter.next(), Some("Slavic"));
+        assert_eq!(iter.next(), Some("Polish"));
+        assert_eq!(iter.next(), Some("Russian"));
+        assert!(iter.next().is_none());
+
+        // How about the other way around (replacing the slavic branch instead
+        slavic.replace_node(&mut arena, germanic).unwrap();
+
+        let mut iter = root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data);
+        assert_eq!(iter.next(), Some("Indo-European"));
+        assert_eq!(iter.next(), Some("Romance"));
+        assert_eq!(iter.next(), Some("French"));
+        assert_eq!(iter.next(), Some("Italian"));
+        assert_eq!(iter.next(), Some("Germanic"));
+        assert_eq!(iter.next(), Some("West"));
+        assert_eq!(iter.next(), Some("Scots"));
+        assert_eq!(iter.next(), Some("English"));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn subtree_tokens_postord() {
+        let root_data = 1usize;
+        let (mut arena, root_token) = Arena::with_data(root_data);
+       
+        let first_child = root_token.append(&mut arena, 2usize);
+        let second_child = root_token.append(&mut arena, 3usize);
+        let third_child = root_token.append(&mut arena, 4usize);
+        let first_grandchild = first_child.append(&mut arena, 0usize);
+        let fourth_child = root_token.append(&mut arena, 5usize);
+        let second_grandchild = second_child.append(&mut arena, 10usize);
+        let third_grandchild = second_child.append(&mut arena, 20usize);
+        let great_grandchild = third_grandchild.append(&mut arena, 20usize);
+       
+        let mut subtree = root_token.subtree_tokens(&arena, TraversalOrder::Post);
+        assert_eq!(subtree.next(), Some(first_grandchild));
+        assert_eq!(subtree.next(), Some(first_child));
+        assert_eq!(subtree.next(), Some(second_grandchild));
+        assert_eq!(subtree.next(), Some(great_grandchild));
+        assert_eq!(subtree.next(), Some(third_grandchild));
+        assert_eq!(subtree.next(), Some(second_child));
+        assert_eq!(subtree.next(), Some(third_child));
+        assert_eq!(subtree.next(), Some(fourth_child));
+        assert_eq!(subtree.next(), Some(root_token));
+        assert!(subtree.next().is_none());
+       
+        let mut subtree = great_grandchild.subtree_tokens(&arena, TraversalOrder::Post);
+        assert_eq!(subtree.next(), Some(great_grandchild));
+        assert!(subtree.next().is_none());
+    }
+
+    #[test]
+    fn subtree_tokens_levelord() {
+        let root_data = 1usize;
+        let (mut arena, root_token) = Arena::with_data(root_data);
+       
+        let first_child = root_token.append(&mut arena, 2usize);
+        let second_child = root_token.append(&mut arena, 3usize);
+        let third_child = root_token.append(&mut arena, 4usize);
+        let first_grandchild = second_child.append(&mut arena, 10usize);
+        let second_grandchild = second_child.append(&mut arena, 20usize);
+        let fourth_child = root_token.append(&mut arena, 5usize);
+       
+        let mut subtree = root_token.subtree_tokens(&arena, TraversalOrder::Level);
+        assert_eq!(subtree.next(), Some(root_token));
+        assert_eq!(subtree.next(), Some(first_child));
+        assert_eq!(subtree.next(), Some(second_child));
+        assert_eq!(subtree.next(), Some(third_child));
+        assert_eq!(subtree.next(), Some(fourth_child));
+        assert_eq!(subtree.next(), Some(first_grandchild));
+        assert_eq!(subtree.next(), Some(second_grandchild));
+        assert!(subtree.next().is_none());
+       
+        let mut subtree = second_grandchild.subtree_tokens(&arena, TraversalOrder::Level);
+        assert_eq!(subtree.next(), Some(second_grandchild));
+        assert!(subtree.next().is_none());
+    }
+
+    #[test]
+    fn subtree_postord() {
+        let root_data = "Indo-European";
+        let (mut arena, root_token) = Arena::with_data(root_data);
+       
+        root_token.append(&mut arena, "Romance");
+        root_token.append(&mut arena, "Germanic");
+        let third_child = root_token.append(&mut arena, "Celtic");
+        root_token.append(&mut arena, "Slavic");
+        third_child.append(&mut arena, "Ulster");
+        third_child.append(&mut arena, "Gaulish");
+       
+        let mut subtree = root_token.subtree(&arena, TraversalOrder::Post);
+        assert_eq!(subtree.next().unwrap().data, "Romance");
+        assert_eq!(subtree.next().unwrap().data, "Germanic");
+        assert_eq!(subtree.next().unwrap().data, "Ulster");
+        assert_eq!(subtree.next().unwrap().data, "Gaulish");
+        assert_eq!(subtree.next().unwrap().data, "Celtic");
+        assert_eq!(subtree.next().unwrap().data, "Slavic");
+        assert_eq!(subtree.next().unwrap().data, "Indo-European");
+        assert!(subtree.next().is_none());
+    }
+
+    #[test]
+    fn subtree_levelord() {
+        let root_data = "Indo-European";
+        let (mut arena, root_token) = Arena::with_data(root_data);
+       
+        root_token.append(&mut arena, "Romance");
+        root_token.append(&mut arena, "Germanic");
+        let third_child = root_token.append(&mut arena, "Slavic");
+        root_token.append(&mut arena, "Hellenic");
+        third_child.append(&mut arena, "Russian");
+        third_child.append(&mut arena, "Ukrainian");
+       
+        let mut subtree = root_token.subtree(&arena, TraversalOrder::Level);
+        assert_eq!(subtree.next().unwrap().data, "Indo-European");
+        assert_eq!(subtree.next().unwrap().data, "Romance");
+        assert_eq!(subtree.next().unwrap().data, "Germanic");
+        assert_eq!(subtree.next().unwrap().data, "Slavic");
+        assert_eq!(subtree.next().unwrap().data, "Hellenic");
+        assert_eq!(subtree.next().unwrap().data, "Russian");
+        assert_eq!(subtree.next().unwrap().data, "Ukrainian");
+        assert!(subtree.next().is_none());
+    }
+
+    #[test]
+    fn subtree_postord_mut() {
+        let root_data = 1usize;
+        let (mut arena, root_token) = Arena::with_data(root_data);
+       
+        root_token.append(&mut arena, 2usize);
+        root_token.append(&mut arena, 3usize);
+        let third_child = root_token.append(&mut arena, 4usize);
+        root_token.append(&mut arena, 5usize);
+        third_child.append(&mut arena, 10usize);
+        third_child.append(&mut arena, 20usize);
+       
+        for x in root_token.subtree_mut(&mut arena, TraversalOrder::Post) {
+            x.data += 100;
+        }
+       
+        let mut subtree = root_token.subtree(&arena, TraversalOrder::Post);
+        assert_eq!(subtree.next().unwrap().data, 102);
+        assert_eq!(subtree.next().unwrap().data, 103);
+        assert_eq!(subtree.next().unwrap().data, 110);
+        assert_eq!(subtree.next().unwrap().data, 120);
+        assert_eq!(subtree.next().unwrap().data, 104);
+        assert_eq!(subtree.next().unwrap().data, 105);
+        assert_eq!(subtree.next().unwrap().data, 101);
+        assert!(subtree.next().is_none());
+    }
+
+    #[test]
+    fn subtree_levelord_mut() {
+        let root_data = 1usize;
+        let (mut arena, root_token) = Arena::with_data(root_data);
+       
+        root_token.append(&mut arena, 2usize);
+        root_token.append(&mut arena, 3usize);
+        let third_child = root_token.append(&mut arena, 4usize);
+        root_token.append(&mut arena, 5usize);
+        third_child.append(&mut arena, 10usize);
+        third_child.append(&mut arena, 20usize);
+       
+        for x in root_token.subtree_mut(&mut arena, TraversalOrder::Level) {
+            x.data += 100;
+        }
+       
+        let mut subtree = root_token.subtree(&arena, TraversalOrder::Level);
+        assert_eq!(subtree.next().unwrap().data, 101);
+        assert_eq!(subtree.next().unwrap().data, 102);
+        assert_eq!(subtree.next().unwrap().data, 103);
+        assert_eq!(subtree.next().unwrap().data, 104);
+        assert_eq!(subtree.next().unwrap().data, 105);
+        assert_eq!(subtree.next().unwrap().data, 110);
+        assert_eq!(subtree.next().unwrap().data, 120);
+        assert!(subtree.next().is_none());
+    }
+
+    #[test]
+    fn remove_descendants() {
+        let root_data = 1usize;
+        let (mut arena, root_token) = Arena::with_data(root_data);
+
+        let first_child = root_token.append(&mut arena, 2usize);
+        let second_child = root_token.append(&mut arena, 3usize);
+        let third_child = root_token.append(&mut arena, 4usize);
+        let fourth_child = root_token.append(&mut arena, 5usize);
+        let grandchild_1 = third_child.append(&mut arena, 10usize);
+        third_child.append(&mut arena, 20usize);
+        grandchild_1.append(&mut arena, 100usize);
+
+        assert_eq!(arena.node_count(), 8);
+
+        third_child.remove_descendants(&mut arena);
+
+        let mut subtree = root_token.subtree_tokens(&arena, TraversalOrder::Pre);
+        assert_eq!(subtree.next(), Some(root_token));
+        assert_eq!(subtree.next(), Some(first_child));
+        assert_eq!(subtree.next(), Some(second_child));
+        assert_eq!(subtree.next(), Some(third_child));
+        assert_eq!(subtree.next(), Some(fourth_child));
+        assert!(subtree.next().is_none());
+
+        println!("{:?}", arena.allocator);
+        assert_eq!(arena.node_count(), 5);
+    }
+
+    #[test]
+    fn fold_subtree() {
+        let root_data = 1usize;
+        let (mut arena, root_token) = Arena::with_data(root_data);
+
+        let first_child = root_token.append(&mut arena, 2usize);
+        let second_child = root_token.append(&mut arena, 3usize);
+        first_child.append(&mut arena, 10usize);
+        second_child.append(&mut arena, 20usize);
+        second_child.append(&mut arena, 30usize);
+
+        let expected: usize = root_token.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .sum();
+        assert_eq!(expected, 66);
+        for &order in &[TraversalOrder::Pre, TraversalOrder::Post,
+                        TraversalOrder::Level] {
+            let sum = root_token.fold_subtree(&arena, 0, order,
+                                              |acc, x| acc + x.data);
+            assert_eq!(sum, expected);
+        }
+
+        // the order of the fold follows the traversal order
+        let post: Vec<usize> = root_token.fold_subtree(
+            &arena, Vec::new(), TraversalOrder::Post,
+            |mut acc, x| { acc.push(x.data); acc }
+        );
+        assert_eq!(&post[..], &[10, 2, 20, 30, 3, 1]);
+    }
+
+    #[test]
+    fn reduce_bottom_up() {
+        let root_data = 1usize;
+        let (mut arena, root_token) = Arena::with_data(root_data);
+
+        let first_child = root_token.append(&mut arena, 2usize);
+        let second_child = root_token.append(&mut arena, 3usize);
+        let grandchild = first_child.append(&mut arena, 10usize);
+        second_child.append(&mut arena, 20usize);
+        second_child.append(&mut arena, 30usize);
+        grandchild.append(&mut arena, 100usize);
+
+        for &token in &[root_token, first_child, second_child, grandchild] {
+            let size = token.reduce_bottom_up(&arena, |_, sizes: &[usize]| {
+                1 + sizes.iter().sum::<usize>()
+            });
+            let count = token.subtree_tokens(&arena, TraversalOrder::Pre).count();
+            assert_eq!(size, count);
+        }
+
+        // children values are handed over in the order of insertion
+        let repr = root_token.reduce_bottom_up(&arena, |node, children: &[String]| {
+            match children.is_empty() {
+                true => node.data.to_string(),
+                false => format!("{}({})", node.data, children.join(" "))
+            }
+        });
+        assert_eq!(repr, "1(2(10(100)) 3(20 30))");
+    }
+
+    #[test]
+    fn descendants_tokens() {
+        let root_data = 1usize;
+        let (mut arena, root_token) = Arena::with_data(root_data);
+
+        let first_child = root_token.append(&mut arena, 2usize);
+        let second_child = root_token.append(&mut arena, 3usize);
+        first_child.append(&mut arena, 10usize);
+        let grandchild = second_child.append(&mut arena, 20usize);
+        grandchild.append(&mut arena, 100usize);
+
+        for &order in &[TraversalOrder::Pre, TraversalOrder::Post,
+                        TraversalOrder::Level] {
+            for &token in &[root_token, second_child] {
+                let subtree: Vec<_> = token.subtree_tokens(&arena, order)
+                    .filter(|&t| t != token)
+                    .collect();
+                let descendants: Vec<_> = token.descendants_tokens(&arena, order)
+                    .collect();
+                assert!(!descendants.contains(&token));
+                assert_eq!(descendants, subtree);
+                assert_eq!(descendants.len(),
+                           token.subtree_tokens(&arena, order).count() - 1);
+            }
+        }
+
+        let leaf = first_child.children_tokens(&arena).next().unwrap();
+        assert!(leaf.descendants_tokens(&arena, TraversalOrder::Post)
+                .next()
+                .is_none());
+    }
+
+    #[test]
+    fn remove_and_promote_children() {
+        let root_data = 1usize;
+        let (mut arena, root_token) = Arena::with_data(root_data);
+
+        let first_child = root_token.append(&mut arena, 2usize);
+        let second_child = root_token.append(&mut arena, 3usize);
+        let third_child = root_token.append(&mut arena, 4usize);
+        let grandchild1 = second_child.append(&mut arena, 10usize);
+        let grandchild2 = second_child.append(&mut arena, 20usize);
+        let great_grandchild = grandchild2.append(&mut arena, 100usize);
+
+        let promoted = second_child.remove_and_promote_children(&mut arena);
+        assert_eq!(&promoted[..], &[grandchild1, grandchild2]);
+        assert!(arena.get(second_child).is_none());
+        assert_eq!(arena.node_count(), 6);
+
+        let children: Vec<_> = root_token.children_tokens(&arena).collect();
+        assert_eq!(&children[..], &[first_child, grandchild1, grandchild2,
+                                    third_child]);
+        let reversed: Vec<_> = third_child.preceding_siblings_tokens(&arena)
+            .collect();
+        assert_eq!(&reversed[..], &[grandchild2, grandchild1, first_child]);
+        for &child in &children {
+            assert_eq!(arena[child].parent(), Some(root_token));
+        }
+        assert_eq!(arena[great_grandchild].parent(), Some(grandchild2));
+
+        // promoting into the first and last positions
+        first_child.append(&mut arena, 5usize);
+        first_child.remove_and_promote_children(&mut arena);
+        let extra = third_child.append(&mut arena, 6usize);
+        third_child.remove_and_promote_children(&mut arena);
+        let data: Vec<_> = root_token.children(&arena).map(|x| x.data).collect();
+        assert_eq!(&data[..], &[5, 10, 20, 6]);
+        assert_eq!(arena[extra].next_sibling(), None);
+
+        // a leaf simply goes away
+        let leaf = root_token.children_tokens(&arena).next().unwrap();
+        assert!(leaf.remove_and_promote_children(&mut arena).is_empty());
+        assert_eq!(root_token.children_tokens(&arena).count(), 3);
+
+        // the children of a root become standalone trees
+        let promoted = root_token.remove_and_promote_children(&mut arena);
+        assert_eq!(promoted.len(), 3);
+        for &token in &promoted {
+            let node = &arena[token];
+            assert!(node.parent().is_none());
+            assert!(node.previous_sibling().is_none());
+            assert!(node.next_sibling().is_none());
+        }
+    }
+
+    #[test]
+    fn subtree_with_parents() {
+        let root_data = 1usize;
+        let (mut arena, root_token) = Arena::with_data(root_data);
+
+        let first_child = root_token.append(&mut arena, 2usize);
+        let second_child = root_token.append(&mut arena, 3usize);
+        first_child.append(&mut arena, 10usize);
+        let grandchild = second_child.append(&mut arena, 20usize);
+        grandchild.append(&mut arena, 100usize);
+
+        for &order in &[TraversalOrder::Pre, TraversalOrder::Post,
+                        TraversalOrder::Level] {
+            for &token in &[root_token, second_child] {
+                let mut count = 0;
+                for (parent, node) in token.subtree_with_parents(&arena, order) {
+                    match node.token() == token {
+                        true => assert!(parent.is_none()),
+                        false => assert_eq!(parent, node.parent())
+                    }
+                    count += 1;
+                }
+                assert_eq!(count, token.subtree_tokens(&arena, order).count());
+            }
+        }
+    }
+
+    #[test]
+    fn leaves_mut() {
+        let root_data = String::from("Indo-European");
+        let (mut arena, root_token) = Arena::with_data(root_data);
+
+        let germanic = root_token.append(&mut arena, String::from("Germanic"));
+        germanic.append(&mut arena, String::from("English"));
+        germanic.append(&mut arena, String::from("Dutch"));
+        root_token.append(&mut arena, String::from("Hellenic"));
+
+        let mut order = Vec::new();
+        for leaf in root_token.leaves_mut(&mut arena) {
+            leaf.data.push_str(" (leaf)");
+            order.push(leaf.token());
+        }
+        assert_eq!(order.len(), 3);
+
+        let data: Vec<_> = root_token.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data.as_str())
+            .collect();
+        assert_eq!(&data[..], &["Indo-European", "Germanic", "English (leaf)",
+                                "Dutch (leaf)", "Hellenic (leaf)"]);
+        let leaves: Vec<_> = root_token.subtree_tokens(&arena, TraversalOrder::Pre)
+            .filter(|&t| t.is_leaf(&arena))
+            .collect();
+        assert_eq!(order, leaves);
+    }
+
+    #[test]
+    fn bfs_path_to() {
+        let root_data = 0usize;
+        let (mut arena, root_token) = Arena::with_data(root_data);
+
+        let first_child = root_token.append(&mut arena, 1usize);
+        let second_child = root_token.append(&mut arena, 2usize);
+        let grandchild1 = first_child.append(&mut arena, 3usize);
+        let deep_match = grandchild1.append(&mut arena, 9usize);
+        let grandchild2 = second_child.append(&mut arena, 4usize);
+        let shallow_match = grandchild2.insert_after(&mut arena, 9usize);
+        second_child.append(&mut arena, 9usize);
+
+        // the match closest to the root wins even though a deeper one comes
+        // first in pre-order
+        let path = root_token.bfs_path_to(&arena, |x| x.data == 9).unwrap();
+        assert_eq!(&path[..], &[root_token, second_child, shallow_match]);
+
+        // paths start from the node the search starts from
+        let path = first_child.bfs_path_to(&arena, |x| x.data == 9).unwrap();
+        assert_eq!(&path[..], &[first_child, grandchild1, deep_match]);
+
+        // the node itself may match
+        let path = second_child.bfs_path_to(&arena, |_| true).unwrap();
+        assert_eq!(&path[..], &[second_child]);
+
+        assert!(grandchild1.bfs_path_to(&arena, |x| x.data == 4).is_none());
+    }
+
+    #[test]
+    fn root() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let mut token = root;
+        for i in 1..1000 {
+            token = token.append(&mut arena, i);
+        }
+        assert_eq!(token.root(&arena), root);
+        assert_eq!(arena[token].root(&arena), root);
+        assert_eq!(root.root(&arena), root);
+
+        let other = arena.new_node(0);
+        assert_eq!(other.root(&arena), other);
+    }
+
+    #[test]
+    fn duplicate() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let grandchild = child1.append(&mut arena, 3);
+
+        let copy = child1.duplicate(&mut arena);
+        let children: Vec<_> = root.children_tokens(&arena).collect();
+        assert_eq!(&children[..], &[child1, copy, child2]);
+        assert_eq!(arena[copy].data, 1);
+        assert_eq!(arena[copy].parent(), Some(root));
+        assert!(arena[copy].is_leaf());
+        assert_eq!(arena[child1].first_child(), Some(grandchild));
+
+        // duplicating the last child
+        let last = child2.duplicate(&mut arena);
+        let children: Vec<_> = root.children_tokens(&arena).collect();
+        assert_eq!(&children[..], &[child1, copy, child2, last]);
+        assert_eq!(arena.node_count(), 6);
+    }
+
+    #[test]
+    fn last_child() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        assert!(root.last_child(&arena).is_none());
+        let child1 = root.append(&mut arena, 1);
+        assert_eq!(root.last_child(&arena), Some(child1));
+        let child3 = child1.insert_after(&mut arena, 3);
+        assert_eq!(root.last_child(&arena), Some(child3));
+        let child2 = child3.insert_before(&mut arena, 2);
+        assert_eq!(root.last_child(&arena), Some(child3));
+        assert_eq!(arena[root].last_child(), Some(child3));
+
+        child3.detach(&mut arena);
+        assert_eq!(root.last_child(&arena), Some(child2));
+        arena.uproot(child2);
+        assert_eq!(root.last_child(&arena), Some(child1));
+        arena.remove(child1);
+        assert!(root.last_child(&arena).is_none());
+    }
+
+    #[test]
+    fn is_sibling_of() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 3);
+        let grandchild = child1.append(&mut arena, 4);
+
+        assert!(child1.is_sibling_of(child2, &arena));
+        assert!(child3.is_sibling_of(child1, &arena));
+        assert!(!child1.is_sibling_of(child1, &arena));
+        assert!(!child2.is_sibling_of(grandchild, &arena));
+        assert!(!root.is_sibling_of(child1, &arena));
+
+        let other_root = arena.new_node(5);
+        assert!(!root.is_sibling_of(other_root, &arena));
+        assert!(!root.is_sibling_of(root, &arena));
+    }
+
+    #[test]
+    fn remove_descendants_where() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let grandchild1 = child1.append(&mut arena, 10);
+        let grandchild2 = child1.append(&mut arena, 11);
+        let grandchild3 = child1.append(&mut arena, 12);
+        grandchild3.append(&mut arena, 13);
+        let grandchild4 = child2.append(&mut arena, 21);
+        let grandchild5 = child2.append(&mut arena, 22);
+
+        // remove the grandchildren with even data (and their descendants)
+        let mut visited = Vec::new();
+        root.remove_descendants_where(&mut arena, |x| {
+            visited.push(x.data);
+            x.data >= 10 && x.data % 2 == 0
+        });
+        visited.sort_unstable();
+        assert_eq!(&visited[..], &[1, 2, 10, 11, 12, 21, 22]);
+
+        assert_eq!(arena.node_count(), 5);
+        let children: Vec<_> = child1.children_tokens(&arena).collect();
+        assert_eq!(&children[..], &[grandchild2]);
+        let children: Vec<_> = child2.children_tokens(&arena).collect();
+        assert_eq!(&children[..], &[grandchild4]);
+        assert!(arena.get(grandchild1).is_none());
+        assert!(arena.get(grandchild5).is_none());
+        assert!(arena[grandchild2].previous_sibling().is_none());
+        assert!(arena[grandchild2].next_sibling().is_none());
+        assert_eq!(arena[child1].last_child(), Some(grandchild2));
+        assert_eq!(arena[child1].next_sibling(), Some(child2));
+    }
+
+    #[test]
+    fn best_first() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 4);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 9);
+        child1.append(&mut arena, 5);
+        child1.append(&mut arena, 1);
+        child2.append(&mut arena, 8).append(&mut arena, 3);
+        child2.append(&mut arena, 6);
+        child3.append(&mut arena, 7);
+
+        // the ascending order is respected whenever the frontier allows it
+        let visited: Vec<_> = root.best_first(&arena, |x, y| x.data.cmp(&y.data))
+            .map(|t| arena[t].data)
+            .collect();
+        assert_eq!(&visited[..], &[0, 2, 4, 1, 5, 6, 8, 3, 9, 7]);
+
+        // a monotonic tree is visited in fully ascending order
+        let (mut arena, root) = Arena::with_data(0usize);
+        let mut tokens = vec![root];
+        let mut seed = 17usize;
+        for i in 1..100 {
+            seed = (seed * 7919 + 13) % 10007;
+            let parent = tokens[seed % i];
+            tokens.push(parent.append(&mut arena, i));
+        }
+        let visited: Vec<_> = root.best_first(&arena, |x, y| x.data.cmp(&y.data))
+            .map(|t| arena[t].data)
+            .collect();
+        assert_eq!(visited, (0..100).collect::<Vec<_>>());
+
+        // reversing the comparator makes it a max-first traversal
+        let visited: Vec<_> = root.best_first(&arena, |x, y| y.data.cmp(&x.data))
+            .map(|t| arena[t].data)
+            .collect();
+        assert_eq!(visited.len(), 100);
+        assert_eq!(visited[0], 0);
+        let children_max = root.children(&arena).map(|x| x.data).max();
+        assert_eq!(Some(visited[1]), children_max);
+    }
+
+    #[test]
+    fn flatten() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 3);
+        child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11).append(&mut arena, 110);
+        child3.append(&mut arena, 30);
+
+        let expected: Vec<_> = child1.subtree_tokens(&arena, TraversalOrder::Pre)
+            .collect();
+        child1.flatten(&mut arena);
+
+        // the flattened node keeps its position
+        let children: Vec<_> = root.children_tokens(&arena).collect();
+        assert_eq!(&children[..], &[child1, child2, child3]);
+
+        let mut chain = vec![child1];
+        while let Some(child) = arena[*chain.last().unwrap()].first_child() {
+            chain.push(child);
+        }
+        assert_eq!(chain, expected);
+        for &token in &chain {
+            assert!(token.children_tokens(&arena).count() <= 1);
+        }
+
+        // flattening the whole tree
+        let expected: Vec<_> = root.subtree_tokens(&arena, TraversalOrder::Pre)
+            .collect();
+        root.flatten(&mut arena);
+        let flattened: Vec<_> = root.subtree_tokens(&arena, TraversalOrder::Pre)
+            .collect();
+        assert_eq!(flattened, expected);
+        for &token in &flattened {
+            assert!(token.children_tokens(&arena).count() <= 1);
+            assert_eq!(arena[token].last_child(), arena[token].first_child());
+        }
+        let data: Vec<_> = root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[0, 1, 10, 11, 110, 2, 3, 30]);
+    }
+
+    #[test]
+    fn count_at_depth() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        root.append(&mut arena, 3);
+        let grandchild1 = child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11);
+        child2.append(&mut arena, 20);
+        grandchild1.append(&mut arena, 100);
+
+        assert_eq!(root.count_at_depth(&arena, 0), 1);
+        assert_eq!(root.count_at_depth(&arena, 1), 3);
+        assert_eq!(root.count_at_depth(&arena, 2), 3);
+        assert_eq!(root.count_at_depth(&arena, 3), 1);
+        assert_eq!(root.count_at_depth(&arena, 4), 0);
+        assert_eq!(root.count_at_depth(&arena, 100), 0);
+        assert_eq!(child1.count_at_depth(&arena, 1), 2);
+        assert_eq!(child1.count_at_depth(&arena, 2), 1);
+    }
+
+    #[test]
+    fn sibling_count() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 3);
+        let only_child = child2.append(&mut arena, 20);
+
+        assert_eq!(root.sibling_count(&arena), 0);
+        assert_eq!(only_child.sibling_count(&arena), 0);
+        assert_eq!(child1.sibling_count(&arena), 2);
+        assert_eq!(child2.sibling_count(&arena), 2);
+        assert_eq!(child3.sibling_count(&arena), 2);
+
+        arena.uproot(child3);
+        assert_eq!(child2.sibling_count(&arena), 1);
+
+        // parentless nodes linked as siblings of each other
+        let other = root.insert_after(&mut arena, 4);
+        assert_eq!(root.sibling_count(&arena), 0);
+        assert_eq!(other.sibling_count(&arena), 0);
+        assert!(!root.is_sibling_of(other, &arena));
+    }
+
+    #[test]
+    fn append_with() {
+        struct Entry { token: Option<Token>, name: &'static str }
+
+        let (mut arena, root) = Arena::with_data(Entry { token: None, name: "root" });
+        let mut tokens = Vec::new();
+        for &name in &["a", "b", "c"] {
+            let token = root.append_with(&mut arena, |token| {
+                Entry { token: Some(token), name }
+            });
+            tokens.push(token);
+        }
+        // freed slots are reused
+        arena.uproot(tokens[1]);
+        tokens[1] = tokens[0].append_with(&mut arena, |token| {
+            Entry { token: Some(token), name: "d" }
+        });
+
+        for &token in &tokens {
+            let node = &arena[token];
+            assert_eq!(node.data.token, Some(node.token()));
+        }
+        assert_eq!(arena[tokens[1]].data.name, "d");
+        assert_eq!(arena[tokens[1]].parent(), Some(tokens[0]));
+    }
+
+    #[test]
+    fn weighted_size() {
+        let (mut arena, root) = Arena::with_data(1u64);
+        let child1 = root.append(&mut arena, 2);
+        let child2 = root.append(&mut arena, 3);
+        child1.append(&mut arena, 4);
+        child1.append(&mut arena, 5).append(&mut arena, 6);
+        child2.append(&mut arena, 7);
+
+        let squares = |x: &u64| x * x;
+        assert_eq!(root.weighted_size(&arena, squares), 1 + 4 + 9 + 16 + 25 + 36 + 49);
+        assert_eq!(child1.weighted_size(&arena, squares), 4 + 16 + 25 + 36);
+        assert_eq!(child2.weighted_size(&arena, |_| 1), 2);
+        let count = root.subtree_tokens(&arena, TraversalOrder::Pre).count() as u64;
+        assert_eq!(root.weighted_size(&arena, |_| 1), count);
+    }
+
+    #[test]
+    fn children_chunks() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        assert!(root.children_chunks(&arena, 4).is_empty());
+        let children = arena.append_many_children(root, 1..11);
+
+        for n in 1..15 {
+            let chunks = root.children_chunks(&arena, n);
+            assert_eq!(chunks.len(), n.min(children.len()));
+            let sizes: Vec<_> = chunks.iter().map(|chunk| chunk.len()).collect();
+            let (min, max) = (sizes.iter().min().unwrap(),
+                              sizes.iter().max().unwrap());
+            assert!(max - min <= 1);
+            let flattened: Vec<Token> = chunks.into_iter().flatten().collect();
+            assert_eq!(flattened, children);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn children_chunks_zero() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        root.append(&mut arena, 1);
+        root.children_chunks(&arena, 0);
+    }
+
+    #[test]
+    fn try_append() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child = root.try_append(&mut arena, 1).unwrap();
+        child.append(&mut arena, 2);
+        let capacity = arena.capacity();
+        arena.uproot(child);
+        assert_eq!(arena.node_count(), 1);
+
+        assert_eq!(child.try_append(&mut arena, 3), Err(Error::InvalidToken));
+        assert_eq!(arena.node_count(), 1);
+        assert_eq!(arena.capacity(), capacity);
+        assert!(arena[root].is_leaf());
+
+        let (_, foreign) = Arena::with_data(0usize);
+        assert_eq!(foreign.try_append(&mut arena, 3), Err(Error::InvalidToken));
+        assert_eq!(arena.node_count(), 1);
+    }
+
+    #[test]
+    fn subtree_cloned() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        {
+            let (mut arena, root) = Arena::with_data(vec![0usize]);
+            let child = root.append(&mut arena, vec![1]);
+            child.append(&mut arena, vec![2, 2]);
+            root.append(&mut arena, vec![3]);
+            for data in root.subtree_cloned(&arena, TraversalOrder::Level) {
+                tx.send(data).unwrap();
+            }
         }
+        // the arena is gone but the data lives on
+        drop(tx);
+        let received: Vec<Vec<usize>> = rx.into_iter().collect();
+        assert_eq!(received, vec![vec![0], vec![1], vec![3], vec![2, 2]]);
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    #[test]
+    fn ancestors_inclusive() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let mut token = root;
+        for i in 1..5 {
+            token = token.append(&mut arena, i);
+        }
+
+        let tokens: Vec<_> = token.ancestors_inclusive_tokens(&arena).collect();
+        assert_eq!(tokens.first(), Some(&token));
+        assert_eq!(tokens.last(), Some(&root));
+        assert_eq!(&tokens[1..], &token.ancestors_tokens(&arena).collect::<Vec<_>>()[..]);
+        let data: Vec<_> = token.ancestors_inclusive(&arena).map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[4, 3, 2, 1, 0]);
+
+        let tokens: Vec<_> = root.ancestors_inclusive_tokens(&arena).collect();
+        assert_eq!(&tokens[..], &[root]);
+    }
 
     #[test]
-    #[allow(clippy::cognitive_complexity)]
-    fn replace_node() {
-        // root node that we will attach subtrees to
-        let root_data = "Indo-European";
-        let (mut arena, root) = Arena::with_data(root_data);
-       
-        // the Germanic branch
-        let germanic = root.append(&mut arena, "Germanic");
-        let west = germanic.append(&mut arena, "West");
-        west.append(&mut arena, "Scots");
-        west.append(&mut arena, "English");
-       
-        // the slavic branch
-        let slavic = root.append(&mut arena, "Slavic");
-        slavic.append(&mut arena, "Polish");
-        slavic.append(&mut arena, "Russian");
-       
-        let mut iter = root.subtree(&arena, TraversalOrder::Pre)
-            .map(|x| x.data);
-        assert_eq!(iter.next(), Some("Indo-European"));
-        assert_eq!(iter.next(), Some("Germanic"));
-        assert_eq!(iter.next(), Some("West"));
-        assert_eq!(iter.next(), Some("Scots"));
-        assert_eq!(iter.next(), Some("English"));
-        assert_eq!(iter.next(), Some("Slavic"));
-        assert_eq!(iter.next(), Some("Polish"));
-        assert_eq!(iter.next(), Some("Russian"));
+    fn rotate_up() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 3);
+        let grandchild1 = child2.append(&mut arena, 20);
+        let grandchild2 = child2.append(&mut arena, 21);
+        let great_grandchild = grandchild2.append(&mut arena, 210);
+
+        grandchild2.rotate_up(&mut arena).unwrap();
+        let children: Vec<_> = root.children_tokens(&arena).collect();
+        assert_eq!(&children[..], &[child1, grandchild2, child3]);
+        let children: Vec<_> = grandchild2.children_tokens(&arena).collect();
+        assert_eq!(&children[..], &[great_grandchild, child2]);
+        let children: Vec<_> = child2.children_tokens(&arena).collect();
+        assert_eq!(&children[..], &[grandchild1]);
+        assert_eq!(arena[child2].parent(), Some(grandchild2));
+        assert_eq!(arena.check_integrity(), Ok(()));
+
+        // rotating a child of the root makes it the new root
+        grandchild2.rotate_up(&mut arena).unwrap();
+        assert!(arena[grandchild2].parent().is_none());
+        assert_eq!(arena[root].parent(), Some(grandchild2));
+        assert_eq!(grandchild2.children_tokens(&arena).next_back(), Some(root));
+        let children: Vec<_> = root.children_tokens(&arena).collect();
+        assert_eq!(&children[..], &[child1, child3]);
+        assert_eq!(arena.check_integrity(), Ok(()));
+
+        assert_eq!(grandchild2.rotate_up(&mut arena), Err(Error::NoParent));
+    }
+
+    #[test]
+    fn document_order() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        child1.append(&mut arena, 10).append(&mut arena, 100);
+        child1.append(&mut arena, 11);
+        child2.append(&mut arena, 20);
+        let grandchild = child2.append(&mut arena, 21);
+        grandchild.append(&mut arena, 210);
+        grandchild.append(&mut arena, 211);
+        child2.append(&mut arena, 22);
+        let last = root.append(&mut arena, 3).append(&mut arena, 30);
+
+        let expected: Vec<_> = root.subtree_tokens(&arena, TraversalOrder::Pre)
+            .collect();
+        let mut forward = vec![root];
+        while let Some(next) = forward.last().unwrap().next_in_document_order(&arena) {
+            forward.push(next);
+        }
+        assert_eq!(forward, expected);
+        assert_eq!(forward.last(), Some(&last));
+
+        let mut backward = vec![last];
+        while let Some(previous) = backward.last().unwrap()
+            .previous_in_document_order(&arena) {
+            backward.push(previous);
+        }
+        backward.reverse();
+        assert_eq!(backward, expected);
+    }
+
+    #[test]
+    fn subtree_grouped_by_parent() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        root.append(&mut arena, 3);
+        child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11).append(&mut arena, 110);
+        child2.append(&mut arena, 20);
+
+        let groups = root.subtree_grouped_by_parent(&arena);
+        let internal: Vec<_> = root.subtree_tokens(&arena, TraversalOrder::Pre)
+            .filter(|&token| !token.is_leaf(&arena))
+            .collect();
+        assert_eq!(groups.iter().map(|(token, _)| *token).collect::<Vec<_>>(),
+                   internal);
+        for (token, children) in &groups {
+            assert!(!children.is_empty());
+            assert_eq!(*children, token.children_tokens(&arena).collect::<Vec<_>>());
+        }
+        let edges: usize = groups.iter().map(|(_, children)| children.len()).sum();
+        assert_eq!(edges, arena.node_count() - 1);
+
+        let leaf = arena[child2].first_child().unwrap();
+        assert!(leaf.subtree_grouped_by_parent(&arena).is_empty());
+    }
+
+    #[test]
+    fn mirror() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        root.append(&mut arena, 3);
+        child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11).append(&mut arena, 110);
+        child1.append(&mut arena, 12);
+        child2.append(&mut arena, 20);
+        let original = arena.clone();
+        let pre_order: Vec<_> = root.subtree_tokens(&arena, TraversalOrder::Pre)
+            .collect();
+        let post_order: Vec<_> = root.subtree_tokens(&arena, TraversalOrder::Post)
+            .collect();
+
+        root.mirror(&mut arena);
+        assert_eq!(arena.check_integrity(), Ok(()));
+        let data: Vec<_> = root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[0, 3, 2, 20, 1, 12, 11, 110, 10]);
+        // the pre-order walk of a mirror is the reversed post-order walk of
+        // the original
+        let mirrored: Vec<_> = root.subtree_tokens(&arena, TraversalOrder::Pre)
+            .collect();
+        assert_eq!(mirrored, post_order.into_iter().rev().collect::<Vec<_>>());
+
+        root.mirror(&mut arena);
+        let restored: Vec<_> = root.subtree_tokens(&arena, TraversalOrder::Pre)
+            .collect();
+        assert_eq!(restored, pre_order);
+        assert!(arena == original);
+        assert_eq!(arena.check_integrity(), Ok(()));
+    }
+
+    #[test]
+    fn diameter() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        assert_eq!(root.diameter(&arena), 0);
+        root.append(&mut arena, 1);
+        let child = root.append(&mut arena, 2);
+        assert_eq!(root.diameter(&arena), 2);
+
+        // two long branches hanging from a child of the root: the longest path
+        // goes through the child rather than the root
+        let mut left = child;
+        let mut right = child;
+        for i in 0..4 {
+            left = left.append(&mut arena, 10 + i);
+            right = right.append(&mut arena, 20 + i);
+        }
+        child.append(&mut arena, 3);
+        assert_eq!(child.diameter(&arena), 8);
+        assert_eq!(root.diameter(&arena), 8);
+        assert_eq!(left.diameter(&arena), 0);
+    }
+
+    #[test]
+    fn sibling_at_offset() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 3);
+        let child4 = root.append(&mut arena, 4);
+
+        assert_eq!(child1.sibling_at_offset(&arena, 1), Some(child2));
+        assert_eq!(child1.sibling_at_offset(&arena, 3), Some(child4));
+        assert_eq!(child2.sibling_at_offset(&arena, 2), Some(child4));
+        assert_eq!(child4.sibling_at_offset(&arena, -1), Some(child3));
+        assert_eq!(child4.sibling_at_offset(&arena, -3), Some(child1));
+        assert_eq!(child3.sibling_at_offset(&arena, -2), Some(child1));
+        assert_eq!(child3.sibling_at_offset(&arena, 0), Some(child3));
+        assert_eq!(root.sibling_at_offset(&arena, 0), Some(root));
+
+        assert!(child1.sibling_at_offset(&arena, 4).is_none());
+        assert!(child4.sibling_at_offset(&arena, 1).is_none());
+        assert!(child1.sibling_at_offset(&arena, -1).is_none());
+        assert!(child3.sibling_at_offset(&arena, isize::MIN).is_none());
+        assert!(root.sibling_at_offset(&arena, 1).is_none());
+    }
+
+    #[test]
+    fn edges() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let grandchild1 = child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11);
+        child2.append(&mut arena, 20);
+        grandchild1.append(&mut arena, 100);
+
+        let edges: Vec<_> = root.edges(&arena).collect();
+        assert_eq!(edges.len(),
+                   root.descendants_tokens(&arena, TraversalOrder::Pre).count());
+        for &(parent, child) in &edges {
+            assert_eq!(arena[child].parent(), Some(parent));
+        }
+        let children: Vec<_> = edges.iter().map(|&(_, child)| child).collect();
+        let descendants: Vec<_> = root
+            .descendants_tokens(&arena, TraversalOrder::Pre)
+            .collect();
+        assert_eq!(children, descendants);
+
+        assert_eq!(child2.edges(&arena).count(), 1);
+        let leaf = arena.new_node(3);
+        assert!(leaf.edges(&arena).next().is_none());
+    }
+
+    #[test]
+    fn subtree_contains() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let grandchild = child1.append(&mut arena, 10);
+        let great_grandchild = grandchild.append(&mut arena, 100);
+        let other_root = arena.new_node(3);
+
+        assert!(root.subtree_contains(&arena, root));
+        assert!(grandchild.subtree_contains(&arena, grandchild));
+        assert!(root.subtree_contains(&arena, great_grandchild));
+        assert!(child1.subtree_contains(&arena, great_grandchild));
+        assert!(root.subtree_contains(&arena, child2));
+
+        assert!(!child1.subtree_contains(&arena, child2));
+        assert!(!great_grandchild.subtree_contains(&arena, child1));
+        assert!(!child2.subtree_contains(&arena, great_grandchild));
+        assert!(!root.subtree_contains(&arena, other_root));
+        assert!(!other_root.subtree_contains(&arena, root));
+    }
+
+    #[test]
+    fn subtree_sizes() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        root.append(&mut arena, 3);
+        let grandchild1 = child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11);
+        let grandchild3 = child2.append(&mut arena, 20);
+        grandchild1.append(&mut arena, 100);
+        grandchild1.append(&mut arena, 101);
+        grandchild3.append(&mut arena, 200);
+        arena.new_node(4);
+
+        let sizes = root.subtree_sizes(&arena);
+        assert_eq!(sizes.len(), 10);
+        for token in root.subtree_tokens(&arena, TraversalOrder::Pre) {
+            let count = token.subtree_tokens(&arena, TraversalOrder::Pre).count();
+            assert_eq!(sizes[&token], count);
+        }
+
+        let sizes = child2.subtree_sizes(&arena);
+        assert_eq!(sizes.len(), 3);
+        assert_eq!(sizes[&child2], 3);
+        assert!(!sizes.contains_key(&root));
+    }
+
+    #[test]
+    fn pop_child() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 3);
+        let grandchild = child2.append(&mut arena, 20);
+        grandchild.append(&mut arena, 200);
+        child1.append(&mut arena, 10);
+
+        assert_eq!(root.pop_child(&mut arena), Some((3, child3)));
+        assert_eq!(arena[root].last_child(), Some(child2));
+        assert_eq!(root.pop_child(&mut arena), Some((2, child2)));
+        assert_eq!(arena.node_count(), 3);
+        assert_eq!(root.pop_child(&mut arena), Some((1, child1)));
+        assert_eq!(arena.node_count(), 1);
+        assert!(root.pop_child(&mut arena).is_none());
+        assert!(root.is_leaf(&arena));
+        assert!(arena[root].last_child().is_none());
+
+        // the node can be pushed to again
+        let child4 = root.append(&mut arena, 4);
+        assert_eq!(root.children_tokens(&arena).collect::<Vec<_>>(), [child4]);
+    }
+
+    #[test]
+    fn double_ended_siblings() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let children: Vec<_> = (1..=6).map(|x| root.append(&mut arena, x))
+            .collect();
+
+        let mut iter = children[0].following_siblings_tokens(&arena);
+        assert_eq!(iter.next(), Some(children[1]));
+        assert_eq!(iter.next_back(), Some(children[5]));
+        assert_eq!(iter.next_back(), Some(children[4]));
+        assert_eq!(iter.next(), Some(children[2]));
+        assert_eq!(iter.next(), Some(children[3]));
+        assert!(iter.next_back().is_none());
+        assert!(iter.next().is_none());
+
+        let mut iter = children[5].preceding_siblings_tokens(&arena);
+        assert_eq!(iter.next_back(), Some(children[0]));
+        assert_eq!(iter.next(), Some(children[4]));
+        assert_eq!(iter.next_back(), Some(children[1]));
+        assert_eq!(iter.next_back(), Some(children[2]));
+        assert_eq!(iter.next_back(), Some(children[3]));
+        assert!(iter.next().is_none());
+        assert!(iter.next_back().is_none());
+
+        let data: Vec<_> = children[1].following_siblings(&arena)
+            .rev()
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[6, 5, 4, 3]);
+        let data: Vec<_> = children[3].preceding_siblings(&arena)
+            .rev()
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[1, 2, 3]);
+
+        // the two ends meet on the only sibling left
+        let mut iter = children[4].following_siblings(&arena);
+        assert_eq!(iter.next_back().map(|x| x.data), Some(6));
+        assert!(iter.next().is_none());
+        assert!(children[5].following_siblings_tokens(&arena)
+            .next_back()
+            .is_none());
+        assert!(children[0].preceding_siblings_tokens(&arena)
+            .next_back()
+            .is_none());
+    }
+
+    #[test]
+    fn common_path() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let grandchild1 = child1.append(&mut arena, 10);
+        let grandchild2 = child1.append(&mut arena, 11);
+        let great_grandchild = grandchild1.append(&mut arena, 100);
+        let other_root = arena.new_node(3);
+
+        // siblings share the path down to their parent
+        assert_eq!(grandchild1.common_path(grandchild2, &arena), [root, child1]);
+        assert_eq!(child1.common_path(child2, &arena), [root]);
+        assert_eq!(great_grandchild.common_path(child2, &arena), [root]);
+
+        // an ancestor is the lowest common ancestor
+        assert_eq!(great_grandchild.common_path(child1, &arena), [root, child1]);
+        assert_eq!(child1.common_path(great_grandchild, &arena), [root, child1]);
+        assert_eq!(root.common_path(grandchild2, &arena), [root]);
+        assert_eq!(grandchild1.common_path(grandchild1, &arena),
+                   [root, child1, grandchild1]);
+
+        assert!(child1.common_path(other_root, &arena).is_empty());
+    }
+
+    #[test]
+    fn relative_path() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let grandchild1 = child1.append(&mut arena, 10);
+        let grandchild2 = child1.append(&mut arena, 11);
+        let great_grandchild = grandchild2.append(&mut arena, 110);
+        let other_root = arena.new_node(3);
+
+        // upward only
+        assert_eq!(great_grandchild.relative_path(root, &arena), Some((3, vec![])));
+        assert_eq!(grandchild1.relative_path(child1, &arena), Some((1, vec![])));
+        assert_eq!(child2.relative_path(child2, &arena), Some((0, vec![])));
+
+        // downward only
+        assert_eq!(root.relative_path(great_grandchild, &arena),
+                   Some((0, vec![0, 1, 0])));
+        assert_eq!(root.relative_path(child2, &arena), Some((0, vec![1])));
+
+        // mixed
+        assert_eq!(grandchild1.relative_path(great_grandchild, &arena),
+                   Some((1, vec![1, 0])));
+        assert_eq!(great_grandchild.relative_path(child2, &arena),
+                   Some((3, vec![1])));
+        assert_eq!(child2.relative_path(grandchild1, &arena),
+                   Some((1, vec![0, 0])));
+
+        assert!(child1.relative_path(other_root, &arena).is_none());
+        assert!(other_root.relative_path(root, &arena).is_none());
+    }
+
+    #[test]
+    fn is_only_child() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let grandchild = child1.append(&mut arena, 10);
+        let great_grandchild = grandchild.append(&mut arena, 100);
+
+        assert!(!root.is_only_child(&arena));
+        assert!(child1.is_only_child(&arena));
+        assert!(grandchild.is_only_child(&arena));
+        assert!(great_grandchild.is_only_child(&arena));
+
+        let child2 = root.append(&mut arena, 2);
+        assert!(!child1.is_only_child(&arena));
+        assert!(!child2.is_only_child(&arena));
+        child1.detach(&mut arena);
+        assert!(child2.is_only_child(&arena));
+        assert!(!child1.is_only_child(&arena));
+    }
+
+    #[test]
+    fn internal_count() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 3);
+        let grandchild1 = child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11);
+        child2.append(&mut arena, 20);
+        grandchild1.append(&mut arena, 100);
+
+        assert_eq!(root.internal_count(&arena), 4);
+        assert_eq!(child1.internal_count(&arena), 2);
+        assert_eq!(child3.internal_count(&arena), 0);
+
+        for token in root.subtree_tokens(&arena, TraversalOrder::Pre) {
+            let leaf_count = token.subtree_tokens(&arena, TraversalOrder::Pre)
+                .filter(|&t| t.is_leaf(&arena))
+                .count();
+            let subtree_count = token.subtree_tokens(&arena, TraversalOrder::Pre)
+                .count();
+            assert_eq!(token.internal_count(&arena) + leaf_count, subtree_count);
+        }
+    }
+
+    #[test]
+    fn ancestors_until() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let grandchild = child1.append(&mut arena, 10);
+        let great_grandchild = grandchild.append(&mut arena, 100);
+
+        // direct parent
+        assert!(great_grandchild.ancestors_until(grandchild, &arena)
+            .next()
+            .is_none());
+        // far ancestor
+        let ancestors: Vec<_> = great_grandchild.ancestors_until(root, &arena)
+            .collect();
+        assert_eq!(&ancestors[..], &[grandchild, child1]);
+        // not an ancestor
+        let ancestors: Vec<_> = great_grandchild.ancestors_until(child2, &arena)
+            .collect();
+        assert_eq!(&ancestors[..], &[grandchild, child1, root]);
+        let ancestors: Vec<_> = child1.ancestors_until(great_grandchild, &arena)
+            .collect();
+        assert_eq!(&ancestors[..], &[root]);
+        assert!(root.ancestors_until(child1, &arena).next().is_none());
+    }
+
+    #[test]
+    fn is_ancestor_of() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let grandchild = child1.append(&mut arena, 10);
+        let great_grandchild = grandchild.append(&mut arena, 100);
+        let other_root = arena.new_node(3);
+
+        // direct parent and child
+        assert!(child1.is_ancestor_of(grandchild, &arena));
+        assert!(grandchild.is_descendant_of(child1, &arena));
+        assert!(!grandchild.is_ancestor_of(child1, &arena));
+        assert!(!child1.is_descendant_of(grandchild, &arena));
+
+        // deep descendants
+        assert!(root.is_ancestor_of(great_grandchild, &arena));
+        assert!(great_grandchild.is_descendant_of(root, &arena));
+
+        // unrelated nodes
+        assert!(!child2.is_ancestor_of(great_grandchild, &arena));
+        assert!(!child2.is_descendant_of(child1, &arena));
+        assert!(!other_root.is_ancestor_of(child1, &arena));
+        assert!(!root.is_descendant_of(other_root, &arena));
+
+        // self
+        assert!(!root.is_ancestor_of(root, &arena));
+        assert!(!grandchild.is_descendant_of(grandchild, &arena));
+    }
+
+    #[test]
+    fn outline() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let grandchild1 = child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11);
+        child2.append(&mut arena, 20);
+        grandchild1.append(&mut arena, 100);
+
+        let outline: Vec<_> = root.outline(&arena).collect();
+        assert_eq!(&outline[..], &[(0, 0), (1, 1), (2, 10), (3, 100), (2, 11),
+                                   (1, 2), (2, 20)]);
+        let outline: Vec<_> = child1.outline(&arena).collect();
+        assert_eq!(&outline[..], &[(0, 1), (1, 10), (2, 100), (1, 11)]);
+
+        // the outline outlives changes to the arena
+        let outline = child2.outline(&arena);
+        arena.uproot(child2);
+        assert_eq!(outline.collect::<Vec<_>>(), [(0, 2), (1, 20)]);
+    }
+
+    #[test]
+    fn try_append_node() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child = root.append(&mut arena, 1);
+        let grandchild = child.append(&mut arena, 10);
+        let other_root = arena.new_node(2);
+        let other_child = other_root.append(&mut arena, 20);
+
+        // cycles are refused
+        assert_eq!(grandchild.try_append_node(&mut arena, root),
+                   Err(Error::WouldCycle));
+        assert_eq!(root.try_append_node(&mut arena, root),
+                   Err(Error::WouldCycle));
+        assert_eq!(other_child.try_append_node(&mut arena, other_root),
+                   Err(Error::WouldCycle));
+        assert!(arena[root].parent().is_none());
+        assert_eq!(arena.check_integrity(), Ok(()));
+
+        // non-roots are still refused
+        assert_eq!(grandchild.try_append_node(&mut arena, other_child),
+                   Err(Error::NotARootNode));
+
+        // a legitimate move succeeds
+        child.detach(&mut arena);
+        assert_eq!(other_child.try_append_node(&mut arena, child), Ok(()));
+        let data: Vec<_> = other_root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[2, 20, 1, 10]);
+        assert_eq!(arena.check_integrity(), Ok(()));
+    }
+
+    #[test]
+    fn is_balanced() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 3);
+        let grandchild1 = child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11);
+        child2.append(&mut arena, 20);
+        grandchild1.append(&mut arena, 100);
+
+        // child heights: 2, 1, 0
+        assert!(!root.is_balanced(&arena, 1));
+        assert!(root.is_balanced(&arena, 2));
+        assert!(child1.is_balanced(&arena, 1));
+        assert!(child3.is_balanced(&arena, 0));
+
+        child3.append(&mut arena, 30);
+        assert!(root.is_balanced(&arena, 1));
+        assert!(!root.is_balanced(&arena, 0));
+
+        // an imbalance deep down the tree is caught as well
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        child1.append(&mut arena, 10).append(&mut arena, 100);
+        child1.append(&mut arena, 11);
+        let grandchild = child2.append(&mut arena, 20);
+        grandchild.append(&mut arena, 200);
+        assert!(!child1.is_balanced(&arena, 0));
+        assert!(!root.is_balanced(&arena, 0));
+        assert!(root.is_balanced(&arena, 1));
+    }
+
+    #[test]
+    fn ancestors_mut_matches_ancestors() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child = root.append(&mut arena, 1);
+        let grandchild = child.append(&mut arena, 10);
+        let great_grandchild = grandchild.append(&mut arena, 100);
+
+        for &token in &[root, child, grandchild, great_grandchild] {
+            let expected: Vec<_> = token.ancestors_tokens(&arena).collect();
+            let visited: Vec<_> = token.ancestors_mut(&mut arena)
+                .map(|node| node.token())
+                .collect();
+            assert_eq!(expected, visited);
+            let immutable: Vec<_> = token.ancestors(&arena)
+                .map(|node| node.token())
+                .collect();
+            assert_eq!(immutable, visited);
+        }
+
+        for node in great_grandchild.ancestors_mut(&mut arena) {
+            node.data += 1;
+        }
+        let data: Vec<_> = root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[1, 2, 11, 100]);
+    }
+
+    #[test]
+    fn subtree_with_depth() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        root.append(&mut arena, 3);
+        let grandchild1 = child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11);
+        let grandchild3 = child2.append(&mut arena, 20);
+        let great_grandchild = grandchild1.append(&mut arena, 100);
+        grandchild3.append(&mut arena, 200);
+        great_grandchild.append(&mut arena, 1000);
+
+        for &order in &[TraversalOrder::Pre, TraversalOrder::Post,
+                        TraversalOrder::Level] {
+            for &start in &[root, child1, child2, grandchild1] {
+                let pairs: Vec<_> = start.subtree_with_depth(&arena, order)
+                    .map(|(depth, node)| (depth, node.token()))
+                    .collect();
+                let expected: Vec<_> = start.subtree_tokens(&arena, order)
+                    .map(|token| {
+                        let depth = token.ancestors_tokens(&arena)
+                            .position(|t| t == start)
+                            .map_or(0, |i| i + 1);
+                        (depth, token)
+                    })
+                    .collect();
+                assert_eq!(pairs, expected);
+            }
+            let depths: Vec<_> = root.subtree_with_depth(&arena, order)
+                .filter(|&(_, node)| node.token() == root
+                        || node.parent() == Some(root))
+                .map(|(depth, node)| (node.data, depth))
+                .collect();
+            for (data, depth) in depths {
+                assert_eq!(depth, match data { 0 => 0, _ => 1 });
+            }
+        }
+
+        let pairs: Vec<_> = root.subtree_with_depth(&arena, TraversalOrder::Level)
+            .map(|(depth, node)| (depth, node.data))
+            .collect();
+        assert_eq!(&pairs[..], &[(0, 0), (1, 1), (1, 2), (1, 3), (2, 10), (2, 11),
+                                 (2, 20), (3, 100), (3, 200), (4, 1000)]);
+        let leaf = arena.new_node(4);
+        for &order in &[TraversalOrder::Pre, TraversalOrder::Post,
+                        TraversalOrder::Level] {
+            let pairs: Vec<_> = leaf.subtree_with_depth(&arena, order)
+                .map(|(depth, node)| (depth, node.data))
+                .collect();
+            assert_eq!(&pairs[..], &[(0, 4)]);
+        }
+    }
+
+    #[test]
+    fn double_ended_children() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let children: Vec<_> = (1..=5).map(|x| root.append(&mut arena, x))
+            .collect();
+
+        let mut iter = root.children_tokens(&arena);
+        assert_eq!(iter.next_back(), Some(children[4]));
+        assert_eq!(iter.next(), Some(children[0]));
+        assert_eq!(iter.next(), Some(children[1]));
+        assert_eq!(iter.next_back(), Some(children[3]));
+        assert_eq!(iter.next_back(), Some(children[2]));
         assert!(iter.next().is_none());
+        assert!(iter.next_back().is_none());
 
-        // the Romance branch
-        let romance = arena.new_node("Romance");
-        romance.append(&mut arena, "French");
-        romance.append(&mut arena, "Italian");
-       
-        // replace_node germanic with romance
-        germanic.replace_node(&mut arena, romance).unwrap();
-       
-        let mut iter = root.subtree(&arena, TraversalOrder::Pre)
-            .map(|x| x.data);
-        assert_eq!(iter.next(), Some("Indo-European"));
-        assert_eq!(iter.next(), Some("Romance"));
-        assert_eq!(iter.next(), Some("French"));
-        assert_eq!(iter.next(), Some("Italian"));
-        assert_eq!(iter.next(), Some("Slavic"));
-        assert_eq!(iter.next(), Some("Polish"));
-        assert_eq!(iter.next(), Some("Russian"));
+        // the last few children
+        let data: Vec<_> = root.children(&arena).rev().take(2).map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[5, 4]);
+        let mut iter = root.children(&arena);
+        let front: Vec<_> = iter.by_ref().take(3).map(|x| x.data).collect();
+        let back: Vec<_> = iter.rev().map(|x| x.data).collect();
+        assert_eq!(&front[..], &[1, 2, 3]);
+        assert_eq!(&back[..], &[5, 4]);
+
+        // a single child is only yielded once
+        let only_child = children[0].append(&mut arena, 10);
+        let mut iter = children[0].children_tokens(&arena);
+        assert_eq!(iter.next_back(), Some(only_child));
         assert!(iter.next().is_none());
+        assert!(children[1].children_tokens(&arena).next_back().is_none());
+    }
 
-        // How about the other way around (replacing the slavic branch instead
-        slavic.replace_node(&mut arena, germanic).unwrap();
+    #[test]
+    fn document_position() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        root.append(&mut arena, 3);
+        let grandchild1 = child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11);
+        let grandchild3 = child2.append(&mut arena, 20);
+        grandchild1.append(&mut arena, 100);
+        grandchild3.append(&mut arena, 200);
 
-        let mut iter = root.subtree(&arena, TraversalOrder::Pre)
-            .map(|x| x.data);
-        assert_eq!(iter.next(), Some("Indo-European"));
-        assert_eq!(iter.next(), Some("Romance"));
-        assert_eq!(iter.next(), Some("French"));
-        assert_eq!(iter.next(), Some("Italian"));
-        assert_eq!(iter.next(), Some("Germanic"));
-        assert_eq!(iter.next(), Some("West"));
-        assert_eq!(iter.next(), Some("Scots"));
-        assert_eq!(iter.next(), Some("English"));
+        assert_eq!(root.document_position(&arena), 0);
+        for (i, token) in root.subtree_tokens(&arena, TraversalOrder::Pre)
+            .enumerate() {
+            assert_eq!(token.document_position(&arena), i);
+        }
+        // positions are global to the tree rather than to a subtree
+        assert_eq!(grandchild3.document_position(&arena), 6);
+        let other_root = arena.new_node(4);
+        assert_eq!(other_root.document_position(&arena), 0);
+    }
+
+    #[test]
+    fn children_rev() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        for i in 1..=5 { root.append(&mut arena, i); }
+        let child = root.children_tokens(&arena).nth(2).unwrap();
+        child.append(&mut arena, 30);
+
+        for token in root.subtree_tokens(&arena, TraversalOrder::Pre) {
+            let mut expected: Vec<_> = token.children(&arena)
+                .map(|x| x.data)
+                .collect();
+            expected.reverse();
+            let data: Vec<_> = token.children_rev(&arena).map(|x| x.data)
+                .collect();
+            assert_eq!(data, expected);
+        }
+        assert_eq!(root.children_rev(&arena).map(|x| x.data).collect::<Vec<_>>(),
+                   [5, 4, 3, 2, 1]);
+        assert!(child.children_rev(&arena).nth(1).is_none());
+    }
+
+    #[test]
+    fn exact_size_children() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        assert_eq!(root.children_tokens(&arena).len(), 0);
+        for i in 1..=5 {
+            root.append(&mut arena, i);
+            assert_eq!(root.children_tokens(&arena).len(), i);
+            assert_eq!(root.children(&arena).len(), i);
+        }
+
+        let mut iter = root.children_tokens(&arena);
+        iter.next();
+        assert_eq!(iter.len(), 4);
+        iter.next_back();
+        assert_eq!(iter.len(), 3);
+        iter.next();
+        iter.next();
+        assert_eq!(iter.len(), 1);
+        iter.next_back();
+        assert_eq!(iter.len(), 0);
         assert!(iter.next().is_none());
+        assert_eq!(iter.len(), 0);
+
+        let mut iter = root.children(&arena);
+        assert_eq!(iter.size_hint(), (5, Some(5)));
+        iter.next();
+        iter.next_back();
+        assert_eq!(iter.len(), 3);
+        let data: Vec<_> = iter.map(|x| x.data).collect();
+        assert_eq!(&data[..], &[2, 3, 4]);
     }
 
     #[test]
-    fn subtree_tokens_postord() {
-        let root_data = 1usize;
-        let (mut arena, root_token) = Arena::with_data(root_data);
-       
-        let first_child = root_token.append(&mut arena, 2usize);
-        let second_child = root_token.append(&mut arena, 3usize);
-        let third_child = root_token.append(&mut arena, 4usize);
-        let first_grandchild = first_child.append(&mut arena, 0usize);
-        let fourth_child = root_token.append(&mut arena, 5usize);
-        let second_grandchild = second_child.append(&mut arena, 10usize);
-        let third_grandchild = second_child.append(&mut arena, 20usize);
-        let great_grandchild = third_grandchild.append(&mut arena, 20usize);
-       
-        let mut subtree = root_token.subtree_tokens(&arena, TraversalOrder::Post);
-        assert_eq!(subtree.next(), Some(first_grandchild));
-        assert_eq!(subtree.next(), Some(first_child));
-        assert_eq!(subtree.next(), Some(second_grandchild));
-        assert_eq!(subtree.next(), Some(great_grandchild));
-        assert_eq!(subtree.next(), Some(third_grandchild));
-        assert_eq!(subtree.next(), Some(second_child));
-        assert_eq!(subtree.next(), Some(third_child));
-        assert_eq!(subtree.next(), Some(fourth_child));
-        assert_eq!(subtree.next(), Some(root_token));
-        assert!(subtree.next().is_none());
-       
-        let mut subtree = great_grandchild.subtree_tokens(&arena, TraversalOrder::Post);
-        assert_eq!(subtree.next(), Some(great_grandchild));
-        assert!(subtree.next().is_none());
+    fn subtrees_where_size() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        root.append(&mut arena, 3);
+        let grandchild1 = child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11);
+        let grandchild3 = child2.append(&mut arena, 20);
+        grandchild1.append(&mut arena, 100);
+        grandchild1.append(&mut arena, 101);
+        grandchild3.append(&mut arena, 200);
+
+        assert_eq!(root.subtrees_where_size(&arena, |size| size >= 3),
+                   [root, child1, grandchild1, child2]);
+        assert_eq!(root.subtrees_where_size(&arena, |size| size == 2),
+                   [grandchild3]);
+        assert_eq!(root.subtrees_where_size(&arena, |size| size == 1).len(), 5);
+        assert!(child2.subtrees_where_size(&arena, |size| size > 3).is_empty());
     }
 
     #[test]
-    fn subtree_tokens_levelord() {
-        let root_data = 1usize;
-        let (mut arena, root_token) = Arena::with_data(root_data);
-       
-        let first_child = root_token.append(&mut arena, 2usize);
-        let second_child = root_token.append(&mut arena, 3usize);
-        let third_child = root_token.append(&mut arena, 4usize);
-        let first_grandchild = second_child.append(&mut arena, 10usize);
-        let second_grandchild = second_child.append(&mut arena, 20usize);
-        let fourth_child = root_token.append(&mut arena, 5usize);
-       
-        let mut subtree = root_token.subtree_tokens(&arena, TraversalOrder::Level);
-        assert_eq!(subtree.next(), Some(root_token));
-        assert_eq!(subtree.next(), Some(first_child));
-        assert_eq!(subtree.next(), Some(second_child));
-        assert_eq!(subtree.next(), Some(third_child));
-        assert_eq!(subtree.next(), Some(fourth_child));
-        assert_eq!(subtree.next(), Some(first_grandchild));
-        assert_eq!(subtree.next(), Some(second_grandchild));
-        assert!(subtree.next().is_none());
-       
-        let mut subtree = second_grandchild.subtree_tokens(&arena, TraversalOrder::Level);
-        assert_eq!(subtree.next(), Some(second_grandchild));
-        assert!(subtree.next().is_none());
+    fn extract_matching() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 3);
+        let grandchild1 = child1.append(&mut arena, 10);
+        grandchild1.append(&mut arena, 100);
+        child2.append(&mut arena, 20);
+        let grandchild3 = child3.append(&mut arena, 30);
+        grandchild3.append(&mut arena, 300);
+
+        // nodes with odd data; 1 and 3 shadow the matches below them
+        let extracted = root.extract_matching(&mut arena, |x| x.data % 2 == 1);
+        let data: Vec<Vec<_>> = extracted.iter()
+            .map(|(arena, root)| root.subtree(arena, TraversalOrder::Pre)
+                .map(|x| x.data)
+                .collect())
+            .collect();
+        assert_eq!(data, vec![vec![1, 10, 100], vec![3, 30, 300]]);
+
+        let remaining: Vec<_> = root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&remaining[..], &[0, 2, 20]);
+        assert_eq!(arena.node_count(), 3);
+        assert!(arena.get(child1).is_none());
+        assert!(arena.get(child3).is_none());
+        assert_eq!(arena.check_integrity(), Ok(()));
+
+        assert!(root.extract_matching(&mut arena, |x| x.data > 100).is_empty());
+        let extracted = root.extract_matching(&mut arena, |_| true);
+        assert_eq!(extracted.len(), 1);
+        assert!(arena.is_empty());
     }
 
     #[test]
-    fn subtree_postord() {
-        let root_data = "Indo-European";
-        let (mut arena, root_token) = Arena::with_data(root_data);
-       
-        root_token.append(&mut arena, "Romance");
-        root_token.append(&mut arena, "Germanic");
-        let third_child = root_token.append(&mut arena, "Celtic");
-        root_token.append(&mut arena, "Slavic");
-        third_child.append(&mut arena, "Ulster");
-        third_child.append(&mut arena, "Gaulish");
-       
-        let mut subtree = root_token.subtree(&arena, TraversalOrder::Post);
-        assert_eq!(subtree.next().unwrap().data, "Romance");
-        assert_eq!(subtree.next().unwrap().data, "Germanic");
-        assert_eq!(subtree.next().unwrap().data, "Ulster");
-        assert_eq!(subtree.next().unwrap().data, "Gaulish");
-        assert_eq!(subtree.next().unwrap().data, "Celtic");
-        assert_eq!(subtree.next().unwrap().data, "Slavic");
-        assert_eq!(subtree.next().unwrap().data, "Indo-European");
-        assert!(subtree.next().is_none());
+    fn siblings_tokens() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 3);
+        let only_child = child2.append(&mut arena, 20);
+
+        // a middle child
+        let siblings: Vec<_> = child2.siblings_tokens(&arena).collect();
+        assert_eq!(&siblings[..], &[child1, child2, child3]);
+        let siblings: Vec<_> = child2.siblings_tokens_excluding_self(&arena)
+            .collect();
+        assert_eq!(&siblings[..], &[child1, child3]);
+
+        // the first child
+        let siblings: Vec<_> = child1.siblings_tokens(&arena).collect();
+        assert_eq!(&siblings[..], &[child1, child2, child3]);
+        let siblings: Vec<_> = child1.siblings_tokens_excluding_self(&arena)
+            .collect();
+        assert_eq!(&siblings[..], &[child2, child3]);
+
+        // an only child and a root
+        for &token in &[only_child, root] {
+            let siblings: Vec<_> = token.siblings_tokens(&arena).collect();
+            assert_eq!(&siblings[..], &[token]);
+            assert!(token.siblings_tokens_excluding_self(&arena).next().is_none());
+        }
+
+        // parentless nodes linked as siblings of each other
+        let (mut arena, first) = Arena::with_data(0usize);
+        let second = first.insert_after(&mut arena, 1);
+        let third = second.insert_after(&mut arena, 2);
+        for &token in &[first, second, third] {
+            let siblings: Vec<_> = token.siblings_tokens(&arena).collect();
+            assert_eq!(&siblings[..], &[token]);
+            assert!(token.siblings_tokens_excluding_self(&arena).next().is_none());
+        }
     }
 
     #[test]
-    fn subtree_levelord() {
-        let root_data = "Indo-European";
-        let (mut arena, root_token) = Arena::with_data(root_data);
-       
-        root_token.append(&mut arena, "Romance");
-        root_token.append(&mut arena, "Germanic");
-        let third_child = root_token.append(&mut arena, "Slavic");
-        root_token.append(&mut arena, "Hellenic");
-        third_child.append(&mut arena, "Russian");
-        third_child.append(&mut arena, "Ukrainian");
-       
-        let mut subtree = root_token.subtree(&arena, TraversalOrder::Level);
-        assert_eq!(subtree.next().unwrap().data, "Indo-European");
-        assert_eq!(subtree.next().unwrap().data, "Romance");
-        assert_eq!(subtree.next().unwrap().data, "Germanic");
-        assert_eq!(subtree.next().unwrap().data, "Slavic");
-        assert_eq!(subtree.next().unwrap().data, "Hellenic");
-        assert_eq!(subtree.next().unwrap().data, "Russian");
-        assert_eq!(subtree.next().unwrap().data, "Ukrainian");
-        assert!(subtree.next().is_none());
+    fn children_count() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        assert_eq!(root.children_count(&arena), 0);
+        assert_eq!(arena[root].children_count(&arena), 0);
+
+        let child = root.append(&mut arena, 1);
+        assert_eq!(root.children_count(&arena), 1);
+        for i in 2..=5 { root.append(&mut arena, i); }
+        assert_eq!(root.children_count(&arena), 5);
+        assert_eq!(arena[root].children_count(&arena), 5);
+        assert_eq!(child.children_count(&arena), 0);
+
+        arena.uproot(child);
+        assert_eq!(root.children_count(&arena), 4);
+        let walked: usize = root.children_tokens(&arena).map(|_| 1).sum();
+        assert_eq!(root.children_count(&arena), walked);
     }
 
     #[test]
-    fn subtree_postord_mut() {
-        let root_data = 1usize;
-        let (mut arena, root_token) = Arena::with_data(root_data);
-       
-        root_token.append(&mut arena, 2usize);
-        root_token.append(&mut arena, 3usize);
-        let third_child = root_token.append(&mut arena, 4usize);
-        root_token.append(&mut arena, 5usize);
-        third_child.append(&mut arena, 10usize);
-        third_child.append(&mut arena, 20usize);
-       
-        for x in root_token.subtree_mut(&mut arena, TraversalOrder::Post) {
-            x.data += 100;
+    fn nth_child() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let children: Vec<_> = (1..=4).map(|x| root.append(&mut arena, x))
+            .collect();
+
+        for (i, &child) in children.iter().enumerate() {
+            assert_eq!(root.nth_child(&arena, i), Some(child));
         }
-       
-        let mut subtree = root_token.subtree(&arena, TraversalOrder::Post);
-        assert_eq!(subtree.next().unwrap().data, 102);
-        assert_eq!(subtree.next().unwrap().data, 103);
-        assert_eq!(subtree.next().unwrap().data, 110);
-        assert_eq!(subtree.next().unwrap().data, 120);
-        assert_eq!(subtree.next().unwrap().data, 104);
-        assert_eq!(subtree.next().unwrap().data, 105);
-        assert_eq!(subtree.next().unwrap().data, 101);
-        assert!(subtree.next().is_none());
+        assert_eq!(root.nth_child(&arena, 3), Some(children[3]));
+        assert!(root.nth_child(&arena, 4).is_none());
+        assert!(root.nth_child(&arena, usize::MAX).is_none());
+        assert!(children[0].nth_child(&arena, 0).is_none());
+        assert!(children[0].nth_child(&arena, 2).is_none());
     }
 
     #[test]
-    fn subtree_levelord_mut() {
-        let root_data = 1usize;
-        let (mut arena, root_token) = Arena::with_data(root_data);
-       
-        root_token.append(&mut arena, 2usize);
-        root_token.append(&mut arena, 3usize);
-        let third_child = root_token.append(&mut arena, 4usize);
-        root_token.append(&mut arena, 5usize);
-        third_child.append(&mut arena, 10usize);
-        third_child.append(&mut arena, 20usize);
-       
-        for x in root_token.subtree_mut(&mut arena, TraversalOrder::Level) {
-            x.data += 100;
+    fn leaf_paths_iter() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        root.append(&mut arena, 3);
+        let grandchild1 = child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11);
+        child2.append(&mut arena, 20);
+        grandchild1.append(&mut arena, 100);
+        grandchild1.append(&mut arena, 101);
+
+        // collect all the paths up front with a depth-first walk
+        fn eager_paths<T>(token: Token, arena: &Arena<T>) -> Vec<Vec<Token>> {
+            let mut paths = Vec::new();
+            let mut stack = vec![vec![token]];
+            while let Some(path) = stack.pop() {
+                let last = *path.last().unwrap();
+                match last.is_leaf(arena) {
+                    true => paths.push(path),
+                    false => for child in last.children_rev(arena) {
+                        let mut child_path = path.clone();
+                        child_path.push(child.token());
+                        stack.push(child_path);
+                    }
+                }
+            }
+            paths
         }
-       
-        let mut subtree = root_token.subtree(&arena, TraversalOrder::Level);
-        assert_eq!(subtree.next().unwrap().data, 101);
-        assert_eq!(subtree.next().unwrap().data, 102);
-        assert_eq!(subtree.next().unwrap().data, 103);
-        assert_eq!(subtree.next().unwrap().data, 104);
-        assert_eq!(subtree.next().unwrap().data, 105);
-        assert_eq!(subtree.next().unwrap().data, 110);
-        assert_eq!(subtree.next().unwrap().data, 120);
-        assert!(subtree.next().is_none());
+
+        for token in root.subtree_tokens(&arena, TraversalOrder::Pre) {
+            let paths: Vec<_> = token.leaf_paths_iter(&arena).collect();
+            assert_eq!(paths, eager_paths(token, &arena));
+        }
+        assert_eq!(root.leaf_paths_iter(&arena).count(), 5);
+        let mut paths = root.leaf_paths_iter(&arena);
+        assert_eq!(paths.next().map(|path| path.len()), Some(4));
     }
 
     #[test]
-    fn remove_descendants() {
-        let root_data = 1usize;
-        let (mut arena, root_token) = Arena::with_data(root_data);
+    fn prepend() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let first = root.prepend(&mut arena, 1);
+        assert_eq!(arena[root].first_child(), Some(first));
+        assert_eq!(arena[root].last_child(), Some(first));
 
-        let first_child = root_token.append(&mut arena, 2usize);
-        let second_child = root_token.append(&mut arena, 3usize);
-        let third_child = root_token.append(&mut arena, 4usize);
-        let fourth_child = root_token.append(&mut arena, 5usize);
-        let grandchild_1 = third_child.append(&mut arena, 10usize);
-        third_child.append(&mut arena, 20usize);
-        grandchild_1.append(&mut arena, 100usize);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 3);
+        let child0 = root.prepend(&mut arena, 0);
+        child2.append(&mut arena, 20);
+        let grandchild = child2.prepend(&mut arena, 19);
+
+        let data: Vec<_> = root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[0, 0, 1, 2, 19, 20, 3]);
+        assert_eq!(root.subtree_tokens(&arena, TraversalOrder::Pre).nth(1),
+                   Some(child0));
+        assert_eq!(arena[child0].previous_sibling(), None);
+        assert_eq!(arena[child0].next_sibling(), Some(first));
+        assert_eq!(arena[first].previous_sibling(), Some(child0));
+        assert_eq!(arena[root].last_child(), Some(child3));
+        assert_eq!(arena[grandchild].parent(), Some(child2));
+        assert_eq!(arena.check_integrity(), Ok(()));
+    }
+
+    #[test]
+    fn insert_child_at() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let leaf = root.insert_child_at(&mut arena, 3, 10);
+        assert_eq!(arena[root].first_child(), Some(leaf));
+        assert_eq!(arena[root].last_child(), Some(leaf));
+
+        root.append(&mut arena, 2);
+        root.append(&mut arena, 4);
+        let front = root.insert_child_at(&mut arena, 0, 1);
+        let middle = root.insert_child_at(&mut arena, 3, 3);
+        let end = root.insert_child_at(&mut arena, 5, 5);
+        let past_end = root.insert_child_at(&mut arena, 100, 6);
+
+        let data: Vec<_> = root.children(&arena).map(|x| x.data).collect();
+        assert_eq!(&data[..], &[1, 10, 2, 3, 4, 5, 6]);
+        assert_eq!(arena[root].first_child(), Some(front));
+        assert_eq!(root.nth_child(&arena, 3), Some(middle));
+        assert_eq!(root.nth_child(&arena, 5), Some(end));
+        assert_eq!(arena[root].last_child(), Some(past_end));
+        assert_eq!(arena[middle].parent(), Some(root));
+
+        let back: Vec<_> = root.children(&arena).rev().map(|x| x.data).collect();
+        assert_eq!(&back[..], &[6, 5, 4, 3, 2, 10, 1]);
+    }
+
+    #[test]
+    fn move_subtree() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 3);
+        let grandchild1 = child1.append(&mut arena, 10);
+        let grandchild2 = child1.append(&mut arena, 11);
+        let great_grandchild = grandchild1.append(&mut arena, 100);
+
+        // cycles are rejected without touching the arena
+        assert_eq!(child1.move_subtree(&mut arena, child1), Err(Error::WouldCycle));
+        assert_eq!(child1.move_subtree(&mut arena, great_grandchild),
+                   Err(Error::WouldCycle));
+        assert_eq!(root.move_subtree(&mut arena, child2), Err(Error::WouldCycle));
+        assert_eq!(arena[child1].parent(), Some(root));
+        assert_eq!(arena[great_grandchild].parent(), Some(grandchild1));
 
+        // move a subtree from the middle of a sibling list
+        assert_eq!(child1.move_subtree(&mut arena, child3), Ok(()));
+        assert_eq!(arena[child1].parent(), Some(child3));
+        assert_eq!(arena[child3].last_child(), Some(child1));
+        assert_eq!(arena[root].first_child(), Some(child2));
+        let subtree: Vec<_> = root.subtree_tokens(&arena, TraversalOrder::Pre)
+            .collect();
+        assert_eq!(&subtree[..], &[root, child2, child3, child1, grandchild1,
+                                   great_grandchild, grandchild2]);
+        assert_eq!(arena[great_grandchild].data, 100);
+
+        // move a root into a tree, and a node within its own parent
+        let other = arena.new_node(4);
+        assert_eq!(other.move_subtree(&mut arena, child2), Ok(()));
+        assert_eq!(grandchild1.move_subtree(&mut arena, child1), Ok(()));
+        let data: Vec<_> = root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[0, 2, 4, 3, 1, 11, 10, 100]);
+        assert_eq!(arena[child1].last_child(), Some(grandchild1));
         assert_eq!(arena.node_count(), 8);
+    }
 
-        third_child.remove_descendants(&mut arena);
+    #[test]
+    fn next_leaf() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        assert!(root.next_leaf(&arena).is_none());
 
-        let mut subtree = root_token.subtree_tokens(&arena, TraversalOrder::Pre);
-        assert_eq!(subtree.next(), Some(root_token));
-        assert_eq!(subtree.next(), Some(first_child));
-        assert_eq!(subtree.next(), Some(second_child));
-        assert_eq!(subtree.next(), Some(third_child));
-        assert_eq!(subtree.next(), Some(fourth_child));
-        assert!(subtree.next().is_none());
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        let child3 = root.append(&mut arena, 3);
+        let grandchild1 = child1.append(&mut arena, 10);
+        let grandchild2 = child1.append(&mut arena, 11);
+        let great_grandchild = grandchild1.append(&mut arena, 100);
+        let grandchild3 = child3.append(&mut arena, 30);
+        let great_grandchild2 = grandchild3.append(&mut arena, 300);
 
-        println!("{:?}", arena.allocator);
-        assert_eq!(arena.node_count(), 5);
+        let mut leaves = Vec::new();
+        let mut token = root;
+        while let Some(leaf) = token.next_leaf(&arena) {
+            leaves.push(leaf);
+            token = leaf;
+        }
+        assert_eq!(&leaves[..], &[great_grandchild, grandchild2, child2,
+                                  great_grandchild2]);
+        let expected: Vec<_> = root.subtree_tokens(&arena, TraversalOrder::Pre)
+            .filter(|&t| t.is_leaf(&arena))
+            .collect();
+        assert_eq!(leaves, expected);
+
+        // starting from internal nodes
+        assert_eq!(child1.next_leaf(&arena), Some(great_grandchild));
+        assert_eq!(grandchild1.next_leaf(&arena), Some(great_grandchild));
+        assert_eq!(child3.next_leaf(&arena), Some(great_grandchild2));
+    }
+
+    #[test]
+    fn total_depth() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        assert_eq!(root.total_depth(&arena), 0);
+
+        let child1 = root.append(&mut arena, 1);
+        let child2 = root.append(&mut arena, 2);
+        root.append(&mut arena, 3);
+        let grandchild1 = child1.append(&mut arena, 10);
+        child1.append(&mut arena, 11);
+        child2.append(&mut arena, 20);
+        grandchild1.append(&mut arena, 100);
+        let great_grandchild = grandchild1.append(&mut arena, 101);
+        great_grandchild.append(&mut arena, 1000);
+
+        let depth_of = |token: Token, top: Token| {
+            token.ancestors_tokens(&arena).take_while(|&t| t != top).count() + 1
+        };
+        let expected: usize = root.subtree_tokens(&arena, TraversalOrder::Pre)
+            .skip(1)
+            .map(|token| depth_of(token, root))
+            .sum();
+        assert_eq!(expected, 3 + 2 * 3 + 3 * 2 + 4);
+        assert_eq!(root.total_depth(&arena), expected);
+
+        let expected: usize = child1.subtree_tokens(&arena, TraversalOrder::Pre)
+            .skip(1)
+            .map(|token| depth_of(token, child1))
+            .sum();
+        assert_eq!(child1.total_depth(&arena), expected);
+        assert_eq!(great_grandchild.total_depth(&arena), 1);
+    }
+
+    #[test]
+    fn sort_children() {
+        let (mut arena, root) = Arena::with_data(0usize);
+        let child3 = root.append(&mut arena, 3);
+        let child1 = root.append(&mut arena, 1);
+        let child4 = root.append(&mut arena, 4);
+        let child2 = root.append(&mut arena, 2);
+        let grandchild1 = child1.append(&mut arena, 12);
+        let grandchild2 = child1.append(&mut arena, 11);
+        let grandchild3 = child4.append(&mut arena, 40);
+
+        root.sort_children_by_key(&mut arena, |node| node.data);
+        let children: Vec<_> = root.children_tokens(&arena).collect();
+        assert_eq!(&children[..], &[child1, child2, child3, child4]);
+        let back: Vec<_> = root.children_tokens(&arena).rev().collect();
+        assert_eq!(&back[..], &[child4, child3, child2, child1]);
+        assert_eq!(arena[root].last_child(), Some(child4));
+        assert_eq!(arena[grandchild3].parent(), Some(child4));
+        let data: Vec<_> = root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[0, 1, 12, 11, 2, 3, 4, 40]);
+
+        // only the children of the given node are reordered
+        root.sort_children_by(&mut arena, |a, b| b.data.cmp(&a.data));
+        let data: Vec<_> = root.subtree(&arena, TraversalOrder::Pre)
+            .map(|x| x.data)
+            .collect();
+        assert_eq!(&data[..], &[0, 4, 40, 3, 2, 1, 12, 11]);
+        child1.sort_children_by_key(&mut arena, |node| node.data);
+        let grandchildren: Vec<_> = child1.children_tokens(&arena).collect();
+        assert_eq!(&grandchildren[..], &[grandchild2, grandchild1]);
+        child3.sort_children_by_key(&mut arena, |node| node.data);
+        assert!(child3.is_leaf(&arena));
+
+        // the sort is stable
+        let (mut arena, root) = Arena::with_data(0usize);
+        let tokens: Vec<_> = [2, 1, 2, 1].iter()
+            .map(|&x| root.append(&mut arena, x))
+            .collect();
+        root.sort_children_by_key(&mut arena, |node| node.data);
+        let children: Vec<_> = root.children_tokens(&arena).collect();
+        assert_eq!(&children[..], &[tokens[1], tokens[3], tokens[0], tokens[2]]);
     }
 }